@@ -0,0 +1,229 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, LitStr};
+
+/// Implements `glsmrs::attributes::Vertex` for a `#[repr(C)]` struct without padding,
+/// every field becomes an attribute of the same name.
+///
+/// Field attributes:
+/// - `#[vertex(rename = "a_position")]` uses given name instead of field name.
+/// - `#[vertex(normalized)]` maps integer components onto `[0, 1]` / `[-1, 1]`.
+/// - `#[vertex(skip)]` keeps field in the buffer but does not bind it.
+#[proc_macro_derive(Vertex, attributes(vertex))]
+pub fn derive_vertex(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    vertex(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// Implements `glsmrs::Uniforms`, every named field becomes a uniform of the same name.
+///
+/// Container attributes:
+/// - `#[uniform(prefix = "light.")]` prepended to every name, handy for GLSL structs.
+///
+/// Field attributes:
+/// - `#[uniform(rename = "u_color")]` uses given name instead of field name.
+/// - `#[uniform(mat2)]` uploads `[f32; 4]` as `mat2` rather than `vec4`.
+/// - `#[uniform(skip)]` leaves field out.
+#[proc_macro_derive(Uniforms, attributes(uniform))]
+pub fn derive_uniforms(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    uniforms(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+#[derive(Default)]
+struct UniformAttrs {
+    prefix: Option<String>,
+    rename: Option<String>,
+    mat2: bool,
+    skip: bool,
+}
+
+/// Parses `#[uniform(...)]`, `prefix` belongs to the struct, other keys to its fields.
+fn uniform_attrs(attrs: &[syn::Attribute], container: bool) -> syn::Result<UniformAttrs> {
+    let mut result = UniformAttrs::default();
+    for attr in attrs.iter().filter(|a| a.path().is_ident("uniform")) {
+        attr.parse_nested_meta(|meta| {
+            let field_key = ["rename", "mat2", "skip"].iter().any(|k| meta.path.is_ident(k));
+            if container && field_key {
+                return Err(meta.error("`rename`, `mat2` and `skip` are field attributes"));
+            }
+            if !container && meta.path.is_ident("prefix") {
+                return Err(meta.error("`prefix` is a struct attribute"));
+            }
+
+            if meta.path.is_ident("prefix") {
+                result.prefix = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("rename") {
+                result.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("mat2") {
+                result.mat2 = true;
+            } else if meta.path.is_ident("skip") {
+                result.skip = true;
+            } else if container {
+                return Err(meta.error("expected `prefix`"));
+            } else {
+                return Err(meta.error("expected `rename`, `mat2` or `skip`"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(result)
+}
+
+fn uniforms(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(s) => match &s.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(syn::Error::new_spanned(&input.ident, "Uniforms needs a struct with named fields")),
+        },
+        _ => return Err(syn::Error::new_spanned(&input.ident, "Uniforms can only be derived for structs")),
+    };
+    let prefix = uniform_attrs(&input.attrs, true)?.prefix.unwrap_or_default();
+
+    let mut entries = vec![];
+    for field in fields {
+        let attrs = uniform_attrs(&field.attrs, false)?;
+        if attrs.skip {
+            continue;
+        }
+        let ident = field.ident.as_ref().expect("named field");
+        let name = format!("{}{}", prefix, attrs.rename.unwrap_or_else(|| ident.to_string()));
+        entries.push(if attrs.mat2 {
+            quote! { (#name, ::glsmrs::UniformData::Matrix2(self.#ident)) }
+        } else {
+            quote! { (#name, ::glsmrs::AsUniformData::as_uniform_data(&mut self.#ident)) }
+        });
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::glsmrs::Uniforms for #ident #ty_generics #where_clause {
+            fn uniforms(&mut self) -> ::std::vec::Vec<(&'static str, ::glsmrs::UniformData<'_>)> {
+                ::std::vec![#(#entries),*]
+            }
+        }
+    })
+}
+
+#[derive(Default)]
+struct VertexAttrs {
+    rename: Option<String>,
+    normalized: bool,
+    skip: bool,
+}
+
+fn vertex_attrs(attrs: &[syn::Attribute]) -> syn::Result<VertexAttrs> {
+    let mut result = VertexAttrs::default();
+    for attr in attrs.iter().filter(|a| a.path().is_ident("vertex")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                result.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("normalized") {
+                result.normalized = true;
+            } else if meta.path.is_ident("skip") {
+                result.skip = true;
+            } else {
+                return Err(meta.error("expected `rename`, `normalized` or `skip`"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(result)
+}
+
+fn is_repr_c(attrs: &[syn::Attribute]) -> bool {
+    let mut repr_c = false;
+    for attr in attrs.iter().filter(|a| a.path().is_ident("repr")) {
+        let _ = attr.parse_nested_meta(|meta| {
+            repr_c |= meta.path.is_ident("C");
+            Ok(())
+        });
+    }
+    repr_c
+}
+
+fn vertex(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let ident = &input.ident;
+    let fields = match &input.data {
+        Data::Struct(s) => match &s.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(syn::Error::new_spanned(ident, "Vertex needs a struct with named fields")),
+        },
+        _ => return Err(syn::Error::new_spanned(ident, "Vertex can only be derived for structs")),
+    };
+    if !is_repr_c(&input.attrs) {
+        return Err(syn::Error::new_spanned(ident, "Vertex needs #[repr(C)] for a stable field layout"));
+    }
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&input.generics, "Vertex can not be derived for generic structs"));
+    }
+
+    let mut attributes = vec![];
+    let mut sizes = vec![];
+    for field in fields {
+        let attrs = vertex_attrs(&field.attrs)?;
+        let ty = &field.ty;
+        sizes.push(quote! { ::std::mem::size_of::<#ty>() });
+        if attrs.skip {
+            continue;
+        }
+        let field_ident = field.ident.as_ref().expect("named field");
+        let name = attrs.rename.unwrap_or_else(|| field_ident.to_string());
+        let normalized = attrs.normalized;
+        attributes.push(quote! {
+            ::glsmrs::attributes::VertexAttribute {
+                name: #name,
+                size: <#ty as ::glsmrs::attributes::VertexComponent>::SIZE,
+                data_type: <#ty as ::glsmrs::attributes::VertexComponent>::DATA_TYPE,
+                normalized: #normalized,
+                offset: ::std::mem::offset_of!(#ident, #field_ident),
+                columns: <#ty as ::glsmrs::attributes::VertexComponent>::COLUMNS,
+            }
+        });
+    }
+
+    Ok(quote! {
+        const _: () = ::std::assert!(
+            ::std::mem::size_of::<#ident>() == 0 #(+ #sizes)*,
+            "Vertex struct must not contain padding",
+        );
+
+        unsafe impl ::glsmrs::attributes::Vertex for #ident {
+            fn attributes() -> ::std::vec::Vec<::glsmrs::attributes::VertexAttribute> {
+                ::std::vec![#(#attributes),*]
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    fn error(input: DeriveInput) -> String {
+        uniforms(input).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn uniform_keys_are_checked_for_placement() {
+        assert_eq!(
+            error(parse_quote! { struct Light { #[uniform(prefix = "light.")] color: [f32; 3] } }),
+            "`prefix` is a struct attribute",
+        );
+        assert_eq!(
+            error(parse_quote! { #[uniform(skip)] struct Light { color: [f32; 3] } }),
+            "`rename`, `mat2` and `skip` are field attributes",
+        );
+        assert_eq!(
+            error(parse_quote! { struct Light { #[uniform(renamed = "u_color")] color: [f32; 3] } }),
+            "expected `rename`, `mat2` or `skip`",
+        );
+        assert!(uniforms(parse_quote! {
+            #[uniform(prefix = "light.")]
+            struct Light { #[uniform(rename = "color")] c: [f32; 3], #[uniform(skip)] cached: bool }
+        }).is_ok());
+    }
+}
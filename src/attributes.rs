@@ -1,22 +1,59 @@
+use crate::GL;
+
 pub enum AttributeType {
     Scal(AttributeScalar),
     Vec2(AttributeVector2),
     Vec3(AttributeVector3),
+    Vec4(AttributeVector4),
+    Color(AttributeColor),
+    /// Normalized `i16` vector.
+    Short(AttributeQuantized),
+    /// Normalized `u16` vector.
+    UShort(AttributeQuantized),
+    Mat4(AttributeMatrix4),
 }
 
 impl AttributeType {
+    /// Components per location, matrices take a location per column.
     pub fn num_components(&self) -> i32 {
         match self {
             AttributeType::Scal(_) => 1,
             AttributeType::Vec2(_) => 2,
             AttributeType::Vec3(_) => 3,
+            AttributeType::Vec4(_) | AttributeType::Color(_) | AttributeType::Mat4(_) => 4,
+            AttributeType::Short(q) | AttributeType::UShort(q) => q.size,
         }
     }
+
+    pub fn columns(&self) -> i32 {
+        match self {
+            AttributeType::Mat4(_) => 4,
+            _ => 1,
+        }
+    }
+
+    pub fn data_type(&self) -> u32 {
+        match self {
+            AttributeType::Color(_) => GL::UNSIGNED_BYTE,
+            AttributeType::Short(_) => GL::SHORT,
+            AttributeType::UShort(_) => GL::UNSIGNED_SHORT,
+            _ => GL::FLOAT,
+        }
+    }
+
+    pub fn normalized(&self) -> bool {
+        self.data_type() != GL::FLOAT
+    }
+
+    pub fn gl_type(&self) -> u32 {
+        self.layout("").gl_type()
+    }
 }
 
 pub trait Attribute {
     type Repr: ?Sized;
 
+    #[allow(clippy::new_ret_no_self)]
     fn new(name: &'static str) -> AttributeType;
 
     fn pack(data: &Self::Repr) -> Vec<u8>;
@@ -61,3 +98,219 @@ impl Attribute for AttributeVector3 {
         data.iter().flat_map(|ee| ee.iter().flat_map(|e| e.to_ne_bytes())).collect::<Vec<u8>>()
     }
 }
+
+pub struct AttributeVector4(pub &'static str);
+
+impl Attribute for AttributeVector4 {
+    type Repr = [[f32; 4]];
+
+    fn new(name: &'static str) -> AttributeType {
+        AttributeType::Vec4(AttributeVector4(name))
+    }
+    fn pack(data: &Self::Repr) -> Vec<u8> {
+        data.iter().flat_map(|ee| ee.iter().flat_map(|e| e.to_ne_bytes())).collect::<Vec<u8>>()
+    }
+}
+
+/// RGBA8 color, read in shader as `vec4` in `[0, 1]` range.
+pub struct AttributeColor(pub &'static str);
+
+impl Attribute for AttributeColor {
+    type Repr = [[u8; 4]];
+
+    fn new(name: &'static str) -> AttributeType {
+        AttributeType::Color(AttributeColor(name))
+    }
+    fn pack(data: &Self::Repr) -> Vec<u8> {
+        data.iter().flatten().copied().collect::<Vec<u8>>()
+    }
+}
+
+/// Attribute of `AttributeShort<N>` / `AttributeUShort<N>`, `size` is `N`.
+pub struct AttributeQuantized {
+    pub name: &'static str,
+    pub size: i32,
+}
+
+/// Quantized data, read in shader as `N` component vector in `[-1, 1]` range.
+pub struct AttributeShort<const N: usize>(pub &'static str);
+
+impl<const N: usize> AttributeShort<N> {
+    const COMPONENTS: i32 = {
+        assert!(1 <= N && N <= 4, "attribute vector has 1 to 4 components");
+        N as i32
+    };
+}
+
+impl<const N: usize> Attribute for AttributeShort<N> {
+    type Repr = [[i16; N]];
+
+    fn new(name: &'static str) -> AttributeType {
+        AttributeType::Short(AttributeQuantized { name, size: Self::COMPONENTS })
+    }
+    fn pack(data: &Self::Repr) -> Vec<u8> {
+        data.iter().flat_map(|ee| ee.iter().flat_map(|e| e.to_ne_bytes())).collect::<Vec<u8>>()
+    }
+}
+
+/// Quantized data, read in shader as `N` component vector in `[0, 1]` range.
+pub struct AttributeUShort<const N: usize>(pub &'static str);
+
+impl<const N: usize> AttributeUShort<N> {
+    const COMPONENTS: i32 = {
+        assert!(1 <= N && N <= 4, "attribute vector has 1 to 4 components");
+        N as i32
+    };
+}
+
+impl<const N: usize> Attribute for AttributeUShort<N> {
+    type Repr = [[u16; N]];
+
+    fn new(name: &'static str) -> AttributeType {
+        AttributeType::UShort(AttributeQuantized { name, size: Self::COMPONENTS })
+    }
+    fn pack(data: &Self::Repr) -> Vec<u8> {
+        data.iter().flat_map(|ee| ee.iter().flat_map(|e| e.to_ne_bytes())).collect::<Vec<u8>>()
+    }
+}
+
+/// Column-major `mat4`, takes four consecutive attribute locations.
+pub struct AttributeMatrix4(pub &'static str);
+
+impl Attribute for AttributeMatrix4 {
+    type Repr = [[f32; 16]];
+
+    fn new(name: &'static str) -> AttributeType {
+        AttributeType::Mat4(AttributeMatrix4(name))
+    }
+    fn pack(data: &Self::Repr) -> Vec<u8> {
+        data.iter().flat_map(|ee| ee.iter().flat_map(|e| e.to_ne_bytes())).collect::<Vec<u8>>()
+    }
+}
+
+/// Placement of a single attribute inside a vertex buffer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VertexAttribute {
+    pub name: &'static str,
+    /// Number of components, 1 to 4.
+    pub size: i32,
+    /// Component type, e.g. `GL::FLOAT` or `GL::UNSIGNED_BYTE`.
+    pub data_type: u32,
+    pub normalized: bool,
+    /// Byte offset of the first component within a vertex.
+    pub offset: usize,
+    /// Matrices take consecutive locations, one per column of `size` components.
+    pub columns: i32,
+}
+
+impl VertexAttribute {
+    /// GL type of the shader attribute, integer data is converted to floats.
+    pub fn gl_type(&self) -> u32 {
+        match (self.columns, self.size) {
+            (2, 2) => GL::FLOAT_MAT2,
+            (3, 3) => GL::FLOAT_MAT3,
+            (4, 4) => GL::FLOAT_MAT4,
+            (_, 2) => GL::FLOAT_VEC2,
+            (_, 3) => GL::FLOAT_VEC3,
+            (_, 4) => GL::FLOAT_VEC4,
+            _ => GL::FLOAT,
+        }
+    }
+
+    /// Size in bytes of a single column.
+    pub fn column_size(&self) -> usize {
+        let component = match self.data_type {
+            GL::BYTE | GL::UNSIGNED_BYTE => 1,
+            GL::SHORT | GL::UNSIGNED_SHORT => 2,
+            _ => 4,
+        };
+        component * self.size as usize
+    }
+}
+
+impl AttributeType {
+    /// Layout of a tightly packed buffer holding only this attribute.
+    pub fn layout(&self, name: &'static str) -> VertexAttribute {
+        VertexAttribute {
+            name,
+            size: self.num_components(),
+            data_type: self.data_type(),
+            normalized: self.normalized(),
+            offset: 0,
+            columns: self.columns(),
+        }
+    }
+}
+
+/// Field type usable in a `Vertex`.
+pub trait VertexComponent {
+    const SIZE: i32;
+    const DATA_TYPE: u32;
+    const COLUMNS: i32 = 1;
+}
+
+macro_rules! vertex_component {
+    ($ty:ty, $data_type:expr) => {
+        impl VertexComponent for $ty {
+            const SIZE: i32 = 1;
+            const DATA_TYPE: u32 = $data_type;
+        }
+
+        impl VertexComponent for [$ty; 2] {
+            const SIZE: i32 = 2;
+            const DATA_TYPE: u32 = $data_type;
+        }
+
+        impl VertexComponent for [$ty; 3] {
+            const SIZE: i32 = 3;
+            const DATA_TYPE: u32 = $data_type;
+        }
+
+        impl VertexComponent for [$ty; 4] {
+            const SIZE: i32 = 4;
+            const DATA_TYPE: u32 = $data_type;
+        }
+    };
+}
+
+vertex_component!(f32, GL::FLOAT);
+vertex_component!(i8, GL::BYTE);
+vertex_component!(u8, GL::UNSIGNED_BYTE);
+vertex_component!(i16, GL::SHORT);
+vertex_component!(u16, GL::UNSIGNED_SHORT);
+
+impl VertexComponent for [[f32; 2]; 2] {
+    const SIZE: i32 = 2;
+    const DATA_TYPE: u32 = GL::FLOAT;
+    const COLUMNS: i32 = 2;
+}
+
+impl VertexComponent for [[f32; 3]; 3] {
+    const SIZE: i32 = 3;
+    const DATA_TYPE: u32 = GL::FLOAT;
+    const COLUMNS: i32 = 3;
+}
+
+impl VertexComponent for [[f32; 4]; 4] {
+    const SIZE: i32 = 4;
+    const DATA_TYPE: u32 = GL::FLOAT;
+    const COLUMNS: i32 = 4;
+}
+
+/// Interleaved vertex, usually implemented with `#[derive(Vertex)]` (`derive` feature).
+///
+/// # Safety
+///
+/// Implementor must be `#[repr(C)]` without padding bytes, so vertices can be uploaded as is,
+/// and `attributes` must describe fields that lie within the struct.
+pub unsafe trait Vertex: Copy {
+    fn attributes() -> Vec<VertexAttribute>;
+}
+
+#[cfg(feature = "derive")]
+pub use glsmrs_derive::Vertex;
+
+pub(crate) fn vertex_bytes<V: Vertex>(vertices: &[V]) -> &[u8] {
+    // safe as `Vertex` guarantees there is no padding
+    unsafe { std::slice::from_raw_parts(vertices.as_ptr() as *const u8, std::mem::size_of_val(vertices)) }
+}
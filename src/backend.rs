@@ -0,0 +1,371 @@
+use std::cell::{Cell, RefCell, Ref};
+use std::collections::HashMap;
+
+pub mod web;
+#[cfg(any(test, feature = "testing"))]
+pub mod mock;
+#[cfg(any(test, feature = "testing"))]
+pub mod soft;
+#[cfg(feature = "glow")]
+pub mod native;
+
+pub use web::WebBackend;
+#[cfg(any(test, feature = "testing"))]
+pub use mock::MockBackend;
+#[cfg(any(test, feature = "testing"))]
+pub use soft::SoftwareBackend;
+#[cfg(feature = "glow")]
+pub use native::NativeBackend;
+
+macro_rules! handle {
+    ($name:ident) => {
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub struct $name(pub u32);
+    };
+}
+
+handle!(BufferId);
+handle!(ShaderId);
+handle!(ProgramId);
+handle!(TextureId);
+handle!(FramebufferId);
+handle!(VertexArrayId);
+handle!(UniformLocation);
+
+/// Uniform value as passed through `GlBackend`, used by backends that keep uniform state themselves.
+#[derive(Clone, Debug, PartialEq)]
+pub enum UniformValue {
+    Int(i32),
+    Float(f32),
+    IntArray(Vec<i32>),
+    FloatArray(Vec<f32>),
+    FloatVec2(Vec<f32>),
+    FloatVec3(Vec<f32>),
+    FloatVec4(Vec<f32>),
+    IntVec2(Vec<i32>),
+    IntVec3(Vec<i32>),
+    IntVec4(Vec<i32>),
+    FloatMat2 { transpose: bool, data: Vec<f32> },
+    FloatMat3 { transpose: bool, data: Vec<f32> },
+    FloatMat4 { transpose: bool, data: Vec<f32> },
+}
+
+/// Active attribute or uniform as reported by `glGetActiveAttrib` / `glGetActiveUniform`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ActiveInfo {
+    pub name: String,
+    pub gl_type: u32,
+    pub size: i32,
+}
+
+/// Set of GL calls the crate relies on.
+///
+/// Resources are referred to by opaque ids handed out by the backend, which keeps the
+/// trait object safe and lets `Ctx` hold any implementation behind an `Rc`.
+/// Parameter queries follow the native `glGet*iv` convention, booleans are reported as `0` / `1`.
+pub trait GlBackend {
+    fn get_extension(&self, name: &str) -> bool;
+    fn is_context_lost(&self) -> bool;
+
+    fn enable(&self, cap: u32);
+    fn disable(&self, cap: u32);
+    fn viewport(&self, x: i32, y: i32, width: i32, height: i32);
+    fn clear_color(&self, r: f32, g: f32, b: f32, a: f32);
+    fn clear_depth(&self, depth: f32);
+    fn clear_stencil(&self, stencil: i32);
+    fn clear(&self, mask: u32);
+    fn blend_equation_separate(&self, mode_rgb: u32, mode_alpha: u32);
+    fn blend_func_separate(&self, src_rgb: u32, dst_rgb: u32, src_alpha: u32, dst_alpha: u32);
+    fn blend_color(&self, r: f32, g: f32, b: f32, a: f32);
+    fn depth_func(&self, func: u32);
+    fn depth_mask(&self, flag: bool);
+    fn cull_face(&self, mode: u32);
+    fn front_face(&self, mode: u32);
+    fn color_mask(&self, r: bool, g: bool, b: bool, a: bool);
+    fn polygon_offset(&self, factor: f32, units: f32);
+    fn stencil_func_separate(&self, face: u32, func: u32, reference: i32, mask: u32);
+    fn stencil_op_separate(&self, face: u32, fail: u32, depth_fail: u32, pass: u32);
+    fn stencil_mask_separate(&self, face: u32, mask: u32);
+    fn scissor(&self, x: i32, y: i32, width: i32, height: i32);
+
+    fn create_shader(&self, shader_type: u32) -> Option<ShaderId>;
+    fn shader_source(&self, shader: ShaderId, source: &str);
+    fn compile_shader(&self, shader: ShaderId);
+    fn get_shader_parameter(&self, shader: ShaderId, pname: u32) -> i32;
+    fn get_shader_info_log(&self, shader: ShaderId) -> Option<String>;
+    fn delete_shader(&self, shader: ShaderId);
+
+    fn create_program(&self) -> Option<ProgramId>;
+    fn attach_shader(&self, program: ProgramId, shader: ShaderId);
+    fn detach_shader(&self, program: ProgramId, shader: ShaderId);
+    fn link_program(&self, program: ProgramId);
+    fn validate_program(&self, program: ProgramId);
+    fn get_program_parameter(&self, program: ProgramId, pname: u32) -> i32;
+    fn get_program_info_log(&self, program: ProgramId) -> Option<String>;
+    fn use_program(&self, program: Option<ProgramId>);
+    fn delete_program(&self, program: ProgramId);
+    fn get_attrib_location(&self, program: ProgramId, name: &str) -> i32;
+    fn get_uniform_location(&self, program: ProgramId, name: &str) -> Option<UniformLocation>;
+    fn get_active_attrib(&self, program: ProgramId, index: u32) -> Option<ActiveInfo>;
+    fn get_active_uniform(&self, program: ProgramId, index: u32) -> Option<ActiveInfo>;
+
+    fn uniform1i(&self, location: UniformLocation, v: i32);
+    fn uniform1f(&self, location: UniformLocation, v: f32);
+    fn uniform1iv(&self, location: UniformLocation, v: &[i32]);
+    fn uniform1fv(&self, location: UniformLocation, v: &[f32]);
+    fn uniform2fv(&self, location: UniformLocation, v: &[f32]);
+    fn uniform3fv(&self, location: UniformLocation, v: &[f32]);
+    fn uniform4fv(&self, location: UniformLocation, v: &[f32]);
+    fn uniform2iv(&self, location: UniformLocation, v: &[i32]);
+    fn uniform3iv(&self, location: UniformLocation, v: &[i32]);
+    fn uniform4iv(&self, location: UniformLocation, v: &[i32]);
+    fn uniform_matrix2fv(&self, location: UniformLocation, transpose: bool, v: &[f32]);
+    fn uniform_matrix3fv(&self, location: UniformLocation, transpose: bool, v: &[f32]);
+    fn uniform_matrix4fv(&self, location: UniformLocation, transpose: bool, v: &[f32]);
+
+    fn create_buffer(&self) -> Option<BufferId>;
+    fn bind_buffer(&self, target: u32, buffer: Option<BufferId>);
+    fn buffer_data(&self, target: u32, data: &[u8], usage: u32);
+    fn buffer_sub_data(&self, target: u32, offset: i32, data: &[u8]);
+    fn delete_buffer(&self, buffer: BufferId);
+
+    fn enable_vertex_attrib_array(&self, index: u32);
+    fn disable_vertex_attrib_array(&self, index: u32);
+    fn vertex_attrib_pointer(&self, index: u32, size: i32, data_type: u32, normalized: bool, stride: i32, offset: i32);
+    fn draw_elements(&self, mode: u32, count: i32, data_type: u32, offset: i32);
+    fn draw_arrays(&self, mode: u32, first: i32, count: i32);
+    // ANGLE_instanced_arrays, only valid once the extension was queried
+    fn vertex_attrib_divisor(&self, index: u32, divisor: u32);
+    fn draw_elements_instanced(&self, mode: u32, count: i32, data_type: u32, offset: i32, instances: i32);
+    fn draw_arrays_instanced(&self, mode: u32, first: i32, count: i32, instances: i32);
+
+    fn create_texture(&self) -> Option<TextureId>;
+    fn active_texture(&self, unit: u32);
+    fn bind_texture(&self, target: u32, texture: Option<TextureId>);
+    #[allow(clippy::too_many_arguments)]
+    fn tex_image_2d(
+        &self,
+        target: u32,
+        level: i32,
+        internal_format: i32,
+        width: i32,
+        height: i32,
+        format: u32,
+        data_type: u32,
+        data: Option<&[u8]>,
+    ) -> Result<(), String>;
+    fn tex_parameteri(&self, target: u32, pname: u32, param: i32);
+    fn delete_texture(&self, texture: TextureId);
+
+    fn create_framebuffer(&self) -> Option<FramebufferId>;
+    fn bind_framebuffer(&self, target: u32, framebuffer: Option<FramebufferId>);
+    fn framebuffer_texture_2d(&self, target: u32, attachment: u32, tex_target: u32, texture: Option<TextureId>, level: i32);
+    fn check_framebuffer_status(&self, target: u32) -> u32;
+    fn delete_framebuffer(&self, framebuffer: FramebufferId);
+
+    // OES_vertex_array_object, only valid once the extension was queried
+    fn create_vertex_array(&self) -> Option<VertexArrayId>;
+    fn bind_vertex_array(&self, vertex_array: Option<VertexArrayId>);
+    fn delete_vertex_array(&self, vertex_array: VertexArrayId);
+}
+
+#[cfg(any(test, feature = "testing"))]
+/// `attribute` / `uniform` declaration found in shader source.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Declaration {
+    pub qualifier: String,
+    pub ty: String,
+    pub name: String,
+    pub size: i32,
+}
+
+#[cfg(any(test, feature = "testing"))]
+impl Declaration {
+    /// GL type enum for declared GLSL type, `0` for structs and unknown types.
+    pub fn gl_type(&self) -> u32 {
+        use crate::GL;
+
+        match self.ty.as_str() {
+            "float" => GL::FLOAT,
+            "vec2" => GL::FLOAT_VEC2,
+            "vec3" => GL::FLOAT_VEC3,
+            "vec4" => GL::FLOAT_VEC4,
+            "int" => GL::INT,
+            "ivec2" => GL::INT_VEC2,
+            "ivec3" => GL::INT_VEC3,
+            "ivec4" => GL::INT_VEC4,
+            "bool" => GL::BOOL,
+            "bvec2" => GL::BOOL_VEC2,
+            "bvec3" => GL::BOOL_VEC3,
+            "bvec4" => GL::BOOL_VEC4,
+            "mat2" => GL::FLOAT_MAT2,
+            "mat3" => GL::FLOAT_MAT3,
+            "mat4" => GL::FLOAT_MAT4,
+            "sampler2D" => GL::SAMPLER_2D,
+            "samplerCube" => GL::SAMPLER_CUBE,
+            _ => 0,
+        }
+    }
+
+    /// Active info the way GL reports it, arrays are named after their first element.
+    pub fn active_info(&self) -> ActiveInfo {
+        ActiveInfo {
+            name: if self.size > 1 { format!("{}[0]", self.name) } else { self.name.clone() },
+            gl_type: self.gl_type(),
+            size: self.size,
+        }
+    }
+
+    /// Attribute locations taken by the declaration, matrices take one per column.
+    pub fn locations(&self) -> i32 {
+        let columns = match self.ty.as_str() {
+            "mat2" => 2,
+            "mat3" => 3,
+            "mat4" => 4,
+            _ => 1,
+        };
+        columns * self.size
+    }
+
+    /// Names `glGetUniformLocation` accepts for this declaration along with element index,
+    /// `name` and `name[0]` both refer to the first element of an array.
+    pub fn location_names(&self) -> Vec<(String, i32)> {
+        let mut names = vec![(self.name.clone(), 0)];
+        if self.size > 1 {
+            names.extend((0..self.size).map(|i| (format!("{}[{}]", self.name, i), i)));
+        }
+        names
+    }
+}
+
+#[cfg(any(test, feature = "testing"))]
+/// `ty name, name[N]` list as found in declarations and struct bodies.
+fn names(ty: &str, names: &[&&str]) -> Vec<(String, String, i32)> {
+    names
+        .iter()
+        .flat_map(|n| n.split(','))
+        .filter(|n| !n.is_empty())
+        .map(|name| match name.find('[') {
+            Some(i) => (
+                ty.to_string(),
+                name[..i].to_string(),
+                name[i + 1..].trim_end_matches(']').parse().unwrap_or(1),
+            ),
+            None => (ty.to_string(), name.to_string(), 1),
+        })
+        .collect()
+}
+
+#[cfg(any(test, feature = "testing"))]
+type Fields = Vec<(String, String, i32)>;
+
+#[cfg(any(test, feature = "testing"))]
+/// `struct Name { ... };` definitions, fields are kept as `(type, name, size)`.
+fn structs(source: &str) -> HashMap<String, Fields> {
+    let mut result = HashMap::new();
+    for chunk in source.split('}') {
+        let Some((head, body)) = chunk.split_once('{') else { continue };
+        let name = match head.split_whitespace().rev().collect::<Vec<_>>()[..] {
+            [name, "struct", ..] => name,
+            _ => continue,
+        };
+        let fields = body
+            .split(';')
+            .flat_map(|field| {
+                let tokens = field
+                    .split_whitespace()
+                    .filter(|t| !matches!(*t, "lowp" | "mediump" | "highp"))
+                    .collect::<Vec<_>>();
+                match tokens.split_first() {
+                    Some((ty, rest)) => names(ty, &rest.iter().collect::<Vec<_>>()),
+                    None => vec![],
+                }
+            })
+            .collect();
+        result.insert(name.to_string(), fields);
+    }
+    result
+}
+
+#[cfg(any(test, feature = "testing"))]
+/// Struct uniforms are reported by GL member by member, e.g. `lights[1].color`.
+fn expand(qualifier: &str, ty: &str, name: String, size: i32, structs: &HashMap<String, Fields>, out: &mut Vec<Declaration>) {
+    match structs.get(ty) {
+        Some(fields) => {
+            let prefixes = if size > 1 { (0..size).map(|i| format!("{}[{}]", name, i)).collect() } else { vec![name] };
+            for prefix in prefixes {
+                for (field_ty, field, field_size) in fields {
+                    expand(qualifier, field_ty, format!("{}.{}", prefix, field), *field_size, structs, out);
+                }
+            }
+        }
+        None => out.push(Declaration {
+            qualifier: qualifier.to_string(),
+            ty: ty.to_string(),
+            name,
+            size,
+        }),
+    }
+}
+
+#[cfg(any(test, feature = "testing"))]
+/// Rough scan of GLSL ES 1.0 source for top-level `attribute` and `uniform` declarations.
+pub(crate) fn declarations(source: &str) -> Vec<Declaration> {
+    let stripped = source
+        .lines()
+        .map(|l| l.split("//").next().unwrap_or(""))
+        .filter(|l| !l.trim_start().starts_with('#'))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let structs = structs(&stripped);
+    let mut result = vec![];
+    for statement in stripped.split([';', '{', '}']) {
+        let tokens = statement.split_whitespace().collect::<Vec<_>>();
+        let qualifier = match tokens.first() {
+            Some(&q) if q == "attribute" || q == "uniform" => q,
+            _ => continue,
+        };
+        let rest = tokens[1..]
+            .iter()
+            .filter(|t| !matches!(**t, "lowp" | "mediump" | "highp"))
+            .collect::<Vec<_>>();
+        if let Some((ty, rest)) = rest.split_first() {
+            for (ty, name, size) in names(ty, rest) {
+                expand(qualifier, &ty, name, size, &structs, &mut result);
+            }
+        }
+    }
+
+    result
+}
+
+/// Id allocator for backends that need to map handles onto their own objects.
+pub(crate) struct Objects<T> {
+    next: Cell<u32>,
+    items: RefCell<HashMap<u32, T>>,
+}
+
+impl<T> Objects<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            next: Cell::new(1),
+            items: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn insert(&self, item: T) -> u32 {
+        let id = self.next.get();
+        self.next.set(id + 1);
+        self.items.borrow_mut().insert(id, item);
+        id
+    }
+
+    pub(crate) fn get(&self, id: u32) -> Option<Ref<'_, T>> {
+        Ref::filter_map(self.items.borrow(), |items| items.get(&id)).ok()
+    }
+
+    pub(crate) fn remove(&self, id: u32) -> Option<T> {
+        self.items.borrow_mut().remove(&id)
+    }
+}
@@ -0,0 +1,802 @@
+use std::cell::{Ref, RefCell};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::GL;
+use super::*;
+
+/// Single GL call as seen by `MockBackend`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Call {
+    GetExtension(String),
+    IsContextLost,
+    Enable(u32),
+    Disable(u32),
+    Viewport(i32, i32, i32, i32),
+    ClearColor([f32; 4]),
+    ClearDepth(f32),
+    ClearStencil(i32),
+    Clear(u32),
+    BlendEquationSeparate { rgb: u32, alpha: u32 },
+    BlendFuncSeparate { src_rgb: u32, dst_rgb: u32, src_alpha: u32, dst_alpha: u32 },
+    BlendColor([f32; 4]),
+    DepthFunc(u32),
+    DepthMask(bool),
+    CullFace(u32),
+    FrontFace(u32),
+    ColorMask([bool; 4]),
+    PolygonOffset { factor: f32, units: f32 },
+    StencilFuncSeparate { face: u32, func: u32, reference: i32, mask: u32 },
+    StencilOpSeparate { face: u32, fail: u32, depth_fail: u32, pass: u32 },
+    StencilMaskSeparate { face: u32, mask: u32 },
+    Scissor(i32, i32, i32, i32),
+    CreateShader(u32, ShaderId),
+    ShaderSource(ShaderId, String),
+    CompileShader(ShaderId),
+    GetShaderParameter(ShaderId, u32),
+    GetShaderInfoLog(ShaderId),
+    DeleteShader(ShaderId),
+    CreateProgram(ProgramId),
+    AttachShader(ProgramId, ShaderId),
+    DetachShader(ProgramId, ShaderId),
+    LinkProgram(ProgramId),
+    ValidateProgram(ProgramId),
+    GetProgramParameter(ProgramId, u32),
+    GetProgramInfoLog(ProgramId),
+    UseProgram(Option<ProgramId>),
+    DeleteProgram(ProgramId),
+    GetAttribLocation(ProgramId, String),
+    GetUniformLocation(ProgramId, String),
+    GetActiveAttrib(ProgramId, u32),
+    GetActiveUniform(ProgramId, u32),
+    Uniform(UniformLocation, UniformValue),
+    CreateBuffer(BufferId),
+    BindBuffer(u32, Option<BufferId>),
+    BufferData { target: u32, size: usize, usage: u32 },
+    BufferSubData { target: u32, offset: i32, size: usize },
+    DeleteBuffer(BufferId),
+    EnableVertexAttribArray(u32),
+    DisableVertexAttribArray(u32),
+    VertexAttribPointer { index: u32, size: i32, data_type: u32, normalized: bool, stride: i32, offset: i32 },
+    DrawElements { mode: u32, count: i32, data_type: u32, offset: i32 },
+    DrawArrays { mode: u32, first: i32, count: i32 },
+    VertexAttribDivisor { index: u32, divisor: u32 },
+    DrawElementsInstanced { mode: u32, count: i32, data_type: u32, offset: i32, instances: i32 },
+    DrawArraysInstanced { mode: u32, first: i32, count: i32, instances: i32 },
+    CreateTexture(TextureId),
+    ActiveTexture(u32),
+    BindTexture(u32, Option<TextureId>),
+    TexImage2D { target: u32, level: i32, internal_format: i32, width: i32, height: i32, format: u32, data_type: u32, size: Option<usize> },
+    TexParameter(u32, u32, i32),
+    DeleteTexture(TextureId),
+    CreateFramebuffer(FramebufferId),
+    BindFramebuffer(u32, Option<FramebufferId>),
+    FramebufferTexture2D { target: u32, attachment: u32, tex_target: u32, texture: Option<TextureId>, level: i32 },
+    CheckFramebufferStatus(u32),
+    DeleteFramebuffer(FramebufferId),
+    CreateVertexArray(VertexArrayId),
+    BindVertexArray(Option<VertexArrayId>),
+    DeleteVertexArray(VertexArrayId),
+}
+
+struct MockShader {
+    shader_type: u32,
+    source: String,
+    compiled: bool,
+    log: Option<String>,
+}
+
+#[derive(Default)]
+struct MockProgram {
+    shaders: Vec<ShaderId>,
+    linked: bool,
+    log: Option<String>,
+    active_attributes: Vec<ActiveInfo>,
+    active_uniforms: Vec<ActiveInfo>,
+}
+
+/// State captured by a vertex array object.
+#[derive(Default)]
+struct MockVertexArray {
+    attrib_arrays: BTreeSet<u32>,
+    divisors: BTreeMap<u32, u32>,
+    element_buffer: Option<BufferId>,
+}
+
+#[derive(Default)]
+struct MockState {
+    calls: Vec<Call>,
+    next_id: u32,
+    missing_extensions: HashSet<String>,
+    compile_error: Option<String>,
+    link_error: Option<String>,
+    context_lost: bool,
+    framebuffer_status: Option<u32>,
+    error: Option<u32>,
+
+    shaders: HashMap<ShaderId, MockShader>,
+    programs: HashMap<ProgramId, MockProgram>,
+    attributes: HashMap<(ProgramId, String), i32>,
+    locations: HashMap<(ProgramId, String), UniformLocation>,
+    buffers: HashSet<BufferId>,
+    textures: HashSet<TextureId>,
+    framebuffers: HashSet<FramebufferId>,
+    vertex_arrays: HashMap<VertexArrayId, MockVertexArray>,
+    default_vertex_array: MockVertexArray,
+
+    program: Option<ProgramId>,
+    framebuffer: Option<FramebufferId>,
+    vertex_array: Option<VertexArrayId>,
+    viewport: [i32; 4],
+    capabilities: BTreeSet<u32>,
+    active_unit: u32,
+    units: HashMap<u32, TextureId>,
+    array_buffer: Option<BufferId>,
+    element_buffer: Option<BufferId>,
+    attrib_arrays: BTreeSet<u32>,
+    divisors: BTreeMap<u32, u32>,
+    uniforms: HashMap<UniformLocation, UniformValue>,
+}
+
+impl MockState {
+    fn id(&mut self) -> u32 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    /// Stores state of currently bound vertex array and loads the one of `next`.
+    fn switch_vertex_array(&mut self, next: Option<VertexArrayId>) {
+        let current = MockVertexArray {
+            attrib_arrays: std::mem::take(&mut self.attrib_arrays),
+            divisors: std::mem::take(&mut self.divisors),
+            element_buffer: self.element_buffer.take(),
+        };
+        match self.vertex_array {
+            Some(id) => {
+                if let Some(v) = self.vertex_arrays.get_mut(&id) {
+                    *v = current;
+                }
+            }
+            None => self.default_vertex_array = current,
+        }
+        let next_state = match next {
+            Some(id) => self.vertex_arrays.get_mut(&id).map(std::mem::take).unwrap_or_default(),
+            None => std::mem::take(&mut self.default_vertex_array),
+        };
+        self.attrib_arrays = next_state.attrib_arrays;
+        self.divisors = next_state.divisors;
+        self.element_buffer = next_state.element_buffer;
+        self.vertex_array = next;
+    }
+}
+
+/// Backend that performs no rendering but records every call and tracks the resulting GL state.
+///
+/// Cloning is cheap and clones share the same log, so one copy can be handed to `Ctx::with_backend`
+/// while another is kept around for assertions.
+#[derive(Clone, Default)]
+pub struct MockBackend(Rc<RefCell<MockState>>);
+
+impl MockBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes `get_extension` report `name` as unavailable.
+    pub fn without_extension(self, name: &str) -> Self {
+        self.0.borrow_mut().missing_extensions.insert(name.to_string());
+        self
+    }
+
+    /// Makes the next `compile_shader` fail with the given info log.
+    pub fn fail_next_compile(&self, log: &str) {
+        self.0.borrow_mut().compile_error = Some(log.to_string());
+    }
+
+    /// Makes `is_context_lost` report lost context from now on.
+    pub fn lose_context(&self) {
+        self.0.borrow_mut().context_lost = true;
+    }
+
+    /// Status reported by `check_framebuffer_status`, `FRAMEBUFFER_COMPLETE` unless overridden.
+    pub fn set_framebuffer_status(&self, status: u32) {
+        self.0.borrow_mut().framebuffer_status = Some(status);
+    }
+
+    /// Makes the next `link_program` fail with the given info log.
+    pub fn fail_next_link(&self, log: &str) {
+        self.0.borrow_mut().link_error = Some(log.to_string());
+    }
+
+    /// First error raised since the last query, `NO_ERROR` if none, clears the flag like `glGetError`.
+    pub fn get_error(&self) -> u32 {
+        self.0.borrow_mut().error.take().unwrap_or(GL::NO_ERROR)
+    }
+
+    pub fn calls(&self) -> Ref<'_, [Call]> {
+        Ref::map(self.0.borrow(), |s| s.calls.as_slice())
+    }
+
+    pub fn take_calls(&self) -> Vec<Call> {
+        std::mem::take(&mut self.0.borrow_mut().calls)
+    }
+
+    pub fn current_program(&self) -> Option<ProgramId> {
+        self.0.borrow().program
+    }
+
+    pub fn current_framebuffer(&self) -> Option<FramebufferId> {
+        self.0.borrow().framebuffer
+    }
+
+    pub fn current_vertex_array(&self) -> Option<VertexArrayId> {
+        self.0.borrow().vertex_array
+    }
+
+    pub fn current_viewport(&self) -> [i32; 4] {
+        self.0.borrow().viewport
+    }
+
+    pub fn is_enabled(&self, cap: u32) -> bool {
+        self.0.borrow().capabilities.contains(&cap)
+    }
+
+    /// Texture bound to `TEXTURE_2D` on unit `GL::TEXTURE0 + unit`.
+    pub fn texture_unit(&self, unit: u32) -> Option<TextureId> {
+        self.0.borrow().units.get(&unit).copied()
+    }
+
+    pub fn enabled_attrib_arrays(&self) -> Vec<u32> {
+        self.0.borrow().attrib_arrays.iter().copied().collect()
+    }
+
+    /// Attribute indices with non-zero divisor.
+    pub fn instanced_attribs(&self) -> Vec<(u32, u32)> {
+        self.0.borrow().divisors.iter().map(|(i, d)| (*i, *d)).collect()
+    }
+
+    /// Last value assigned to uniform `name` of `program`.
+    pub fn uniform(&self, program: ProgramId, name: &str) -> Option<UniformValue> {
+        let s = self.0.borrow();
+        let loc = s.locations.get(&(program, name.to_string()))?;
+        s.uniforms.get(loc).cloned()
+    }
+
+    pub fn live_shaders(&self) -> usize {
+        self.0.borrow().shaders.len()
+    }
+
+    pub fn live_programs(&self) -> usize {
+        self.0.borrow().programs.len()
+    }
+
+    pub fn live_buffers(&self) -> usize {
+        self.0.borrow().buffers.len()
+    }
+
+    pub fn live_textures(&self) -> usize {
+        self.0.borrow().textures.len()
+    }
+
+    pub fn live_framebuffers(&self) -> usize {
+        self.0.borrow().framebuffers.len()
+    }
+
+    pub fn live_vertex_arrays(&self) -> usize {
+        self.0.borrow().vertex_arrays.len()
+    }
+
+    fn record(&self, call: Call) -> std::cell::RefMut<'_, MockState> {
+        let mut s = self.0.borrow_mut();
+        s.calls.push(call);
+        s
+    }
+
+    fn set_uniform(&self, location: UniformLocation, value: UniformValue) {
+        let mut s = self.record(Call::Uniform(location, value.clone()));
+        s.uniforms.insert(location, value);
+    }
+}
+
+impl GlBackend for MockBackend {
+    fn get_extension(&self, name: &str) -> bool {
+        let s = self.record(Call::GetExtension(name.to_string()));
+        !s.missing_extensions.contains(name)
+    }
+
+    fn is_context_lost(&self) -> bool {
+        self.record(Call::IsContextLost).context_lost
+    }
+
+    fn enable(&self, cap: u32) {
+        self.record(Call::Enable(cap)).capabilities.insert(cap);
+    }
+
+    fn disable(&self, cap: u32) {
+        self.record(Call::Disable(cap)).capabilities.remove(&cap);
+    }
+
+    fn viewport(&self, x: i32, y: i32, width: i32, height: i32) {
+        self.record(Call::Viewport(x, y, width, height)).viewport = [x, y, width, height];
+    }
+
+    fn clear_color(&self, r: f32, g: f32, b: f32, a: f32) {
+        self.record(Call::ClearColor([r, g, b, a]));
+    }
+
+    fn clear_depth(&self, depth: f32) {
+        self.record(Call::ClearDepth(depth));
+    }
+
+    fn clear_stencil(&self, stencil: i32) {
+        self.record(Call::ClearStencil(stencil));
+    }
+
+    fn clear(&self, mask: u32) {
+        self.record(Call::Clear(mask));
+    }
+
+    fn blend_equation_separate(&self, mode_rgb: u32, mode_alpha: u32) {
+        self.record(Call::BlendEquationSeparate { rgb: mode_rgb, alpha: mode_alpha });
+    }
+
+    fn blend_func_separate(&self, src_rgb: u32, dst_rgb: u32, src_alpha: u32, dst_alpha: u32) {
+        self.record(Call::BlendFuncSeparate { src_rgb, dst_rgb, src_alpha, dst_alpha });
+    }
+
+    fn blend_color(&self, r: f32, g: f32, b: f32, a: f32) {
+        self.record(Call::BlendColor([r, g, b, a]));
+    }
+
+    fn depth_func(&self, func: u32) {
+        self.record(Call::DepthFunc(func));
+    }
+
+    fn depth_mask(&self, flag: bool) {
+        self.record(Call::DepthMask(flag));
+    }
+
+    fn cull_face(&self, mode: u32) {
+        self.record(Call::CullFace(mode));
+    }
+
+    fn front_face(&self, mode: u32) {
+        self.record(Call::FrontFace(mode));
+    }
+
+    fn color_mask(&self, r: bool, g: bool, b: bool, a: bool) {
+        self.record(Call::ColorMask([r, g, b, a]));
+    }
+
+    fn polygon_offset(&self, factor: f32, units: f32) {
+        self.record(Call::PolygonOffset { factor, units });
+    }
+
+    fn stencil_func_separate(&self, face: u32, func: u32, reference: i32, mask: u32) {
+        self.record(Call::StencilFuncSeparate { face, func, reference, mask });
+    }
+
+    fn stencil_op_separate(&self, face: u32, fail: u32, depth_fail: u32, pass: u32) {
+        self.record(Call::StencilOpSeparate { face, fail, depth_fail, pass });
+    }
+
+    fn stencil_mask_separate(&self, face: u32, mask: u32) {
+        self.record(Call::StencilMaskSeparate { face, mask });
+    }
+
+    fn scissor(&self, x: i32, y: i32, width: i32, height: i32) {
+        self.record(Call::Scissor(x, y, width, height));
+    }
+
+    fn create_shader(&self, shader_type: u32) -> Option<ShaderId> {
+        let mut s = self.0.borrow_mut();
+        let id = ShaderId(s.id());
+        s.shaders.insert(id, MockShader { shader_type, source: String::new(), compiled: false, log: None });
+        s.calls.push(Call::CreateShader(shader_type, id));
+        Some(id)
+    }
+
+    fn shader_source(&self, shader: ShaderId, source: &str) {
+        let mut s = self.record(Call::ShaderSource(shader, source.to_string()));
+        if let Some(sh) = s.shaders.get_mut(&shader) {
+            sh.source = source.to_string();
+        }
+    }
+
+    fn compile_shader(&self, shader: ShaderId) {
+        let mut s = self.record(Call::CompileShader(shader));
+        let error = s.compile_error.take();
+        if let Some(sh) = s.shaders.get_mut(&shader) {
+            sh.compiled = error.is_none();
+            sh.log = error;
+        }
+    }
+
+    fn get_shader_parameter(&self, shader: ShaderId, pname: u32) -> i32 {
+        let s = self.record(Call::GetShaderParameter(shader, pname));
+        match (s.shaders.get(&shader), pname) {
+            (Some(sh), GL::COMPILE_STATUS) => sh.compiled as i32,
+            (Some(sh), GL::SHADER_TYPE) => sh.shader_type as i32,
+            _ => 0,
+        }
+    }
+
+    fn get_shader_info_log(&self, shader: ShaderId) -> Option<String> {
+        let s = self.record(Call::GetShaderInfoLog(shader));
+        s.shaders.get(&shader).map(|sh| sh.log.clone().unwrap_or_default())
+    }
+
+    fn delete_shader(&self, shader: ShaderId) {
+        self.record(Call::DeleteShader(shader)).shaders.remove(&shader);
+    }
+
+    fn create_program(&self) -> Option<ProgramId> {
+        let mut s = self.0.borrow_mut();
+        let id = ProgramId(s.id());
+        s.programs.insert(id, MockProgram::default());
+        s.calls.push(Call::CreateProgram(id));
+        Some(id)
+    }
+
+    fn attach_shader(&self, program: ProgramId, shader: ShaderId) {
+        let mut s = self.record(Call::AttachShader(program, shader));
+        if let Some(p) = s.programs.get_mut(&program) {
+            p.shaders.push(shader);
+        }
+    }
+
+    fn detach_shader(&self, program: ProgramId, shader: ShaderId) {
+        let mut s = self.record(Call::DetachShader(program, shader));
+        if let Some(p) = s.programs.get_mut(&program) {
+            p.shaders.retain(|sh| *sh != shader);
+        }
+    }
+
+    fn link_program(&self, program: ProgramId) {
+        let mut s = self.record(Call::LinkProgram(program));
+        let attached = s
+            .programs
+            .get(&program)
+            .map(|p| p.shaders.iter().filter_map(|sh| s.shaders.get(sh)).collect::<Vec<_>>())
+            .unwrap_or_default();
+        let error = match s.link_error.clone() {
+            Some(log) => Some(log),
+            None if attached.iter().any(|sh| !sh.compiled) => Some("attached shader is not compiled".to_string()),
+            None => None,
+        };
+        let sources = attached.iter().map(|sh| sh.source.clone()).collect::<Vec<_>>();
+        s.link_error = None;
+
+        let mut active_attributes = vec![];
+        let mut active_uniforms: Vec<ActiveInfo> = vec![];
+        let mut next_location = 0;
+        for decl in sources.iter().flat_map(|src| declarations(src)).filter(|_| error.is_none()) {
+            let info = decl.active_info();
+            if decl.qualifier == "attribute" {
+                s.attributes.insert((program, decl.name.clone()), next_location);
+                next_location += decl.locations();
+                active_attributes.push(info);
+            } else if !active_uniforms.contains(&info) {
+                let elements = (0..decl.size).map(|_| UniformLocation(s.id())).collect::<Vec<_>>();
+                for (name, i) in decl.location_names() {
+                    s.locations.insert((program, name), elements[i as usize]);
+                }
+                active_uniforms.push(info);
+            }
+        }
+
+        if let Some(p) = s.programs.get_mut(&program) {
+            p.linked = error.is_none();
+            p.log = error;
+            p.active_attributes = active_attributes;
+            p.active_uniforms = active_uniforms;
+        }
+    }
+
+    fn validate_program(&self, program: ProgramId) {
+        self.record(Call::ValidateProgram(program));
+    }
+
+    fn get_program_parameter(&self, program: ProgramId, pname: u32) -> i32 {
+        let s = self.record(Call::GetProgramParameter(program, pname));
+        match (s.programs.get(&program), pname) {
+            (Some(p), GL::LINK_STATUS) | (Some(p), GL::VALIDATE_STATUS) => p.linked as i32,
+            (Some(p), GL::ATTACHED_SHADERS) => p.shaders.len() as i32,
+            (Some(p), GL::ACTIVE_ATTRIBUTES) => p.active_attributes.len() as i32,
+            (Some(p), GL::ACTIVE_UNIFORMS) => p.active_uniforms.len() as i32,
+            _ => 0,
+        }
+    }
+
+    fn get_program_info_log(&self, program: ProgramId) -> Option<String> {
+        let s = self.record(Call::GetProgramInfoLog(program));
+        s.programs.get(&program).map(|p| p.log.clone().unwrap_or_default())
+    }
+
+    fn use_program(&self, program: Option<ProgramId>) {
+        self.record(Call::UseProgram(program)).program = program;
+    }
+
+    fn delete_program(&self, program: ProgramId) {
+        let mut s = self.record(Call::DeleteProgram(program));
+        s.programs.remove(&program);
+        s.attributes.retain(|(p, _), _| *p != program);
+        s.locations.retain(|(p, _), _| *p != program);
+    }
+
+    fn get_attrib_location(&self, program: ProgramId, name: &str) -> i32 {
+        let s = self.record(Call::GetAttribLocation(program, name.to_string()));
+        s.attributes.get(&(program, name.to_string())).copied().unwrap_or(-1)
+    }
+
+    fn get_uniform_location(&self, program: ProgramId, name: &str) -> Option<UniformLocation> {
+        let s = self.record(Call::GetUniformLocation(program, name.to_string()));
+        s.locations.get(&(program, name.to_string())).copied()
+    }
+
+    fn get_active_attrib(&self, program: ProgramId, index: u32) -> Option<ActiveInfo> {
+        let s = self.record(Call::GetActiveAttrib(program, index));
+        s.programs.get(&program)?.active_attributes.get(index as usize).cloned()
+    }
+
+    fn get_active_uniform(&self, program: ProgramId, index: u32) -> Option<ActiveInfo> {
+        let s = self.record(Call::GetActiveUniform(program, index));
+        s.programs.get(&program)?.active_uniforms.get(index as usize).cloned()
+    }
+
+    fn uniform1i(&self, location: UniformLocation, v: i32) {
+        self.set_uniform(location, UniformValue::Int(v))
+    }
+
+    fn uniform1f(&self, location: UniformLocation, v: f32) {
+        self.set_uniform(location, UniformValue::Float(v))
+    }
+
+    fn uniform1iv(&self, location: UniformLocation, v: &[i32]) {
+        self.set_uniform(location, UniformValue::IntArray(v.to_vec()))
+    }
+
+    fn uniform1fv(&self, location: UniformLocation, v: &[f32]) {
+        self.set_uniform(location, UniformValue::FloatArray(v.to_vec()))
+    }
+
+    fn uniform2fv(&self, location: UniformLocation, v: &[f32]) {
+        self.set_uniform(location, UniformValue::FloatVec2(v.to_vec()))
+    }
+
+    fn uniform3fv(&self, location: UniformLocation, v: &[f32]) {
+        self.set_uniform(location, UniformValue::FloatVec3(v.to_vec()))
+    }
+
+    fn uniform4fv(&self, location: UniformLocation, v: &[f32]) {
+        self.set_uniform(location, UniformValue::FloatVec4(v.to_vec()))
+    }
+
+    fn uniform2iv(&self, location: UniformLocation, v: &[i32]) {
+        self.set_uniform(location, UniformValue::IntVec2(v.to_vec()))
+    }
+
+    fn uniform3iv(&self, location: UniformLocation, v: &[i32]) {
+        self.set_uniform(location, UniformValue::IntVec3(v.to_vec()))
+    }
+
+    fn uniform4iv(&self, location: UniformLocation, v: &[i32]) {
+        self.set_uniform(location, UniformValue::IntVec4(v.to_vec()))
+    }
+
+    fn uniform_matrix2fv(&self, location: UniformLocation, transpose: bool, v: &[f32]) {
+        self.set_uniform(location, UniformValue::FloatMat2 { transpose, data: v.to_vec() })
+    }
+
+    fn uniform_matrix3fv(&self, location: UniformLocation, transpose: bool, v: &[f32]) {
+        self.set_uniform(location, UniformValue::FloatMat3 { transpose, data: v.to_vec() })
+    }
+
+    fn uniform_matrix4fv(&self, location: UniformLocation, transpose: bool, v: &[f32]) {
+        self.set_uniform(location, UniformValue::FloatMat4 { transpose, data: v.to_vec() })
+    }
+
+    fn create_buffer(&self) -> Option<BufferId> {
+        let mut s = self.0.borrow_mut();
+        let id = BufferId(s.id());
+        s.buffers.insert(id);
+        s.calls.push(Call::CreateBuffer(id));
+        Some(id)
+    }
+
+    fn bind_buffer(&self, target: u32, buffer: Option<BufferId>) {
+        let mut s = self.record(Call::BindBuffer(target, buffer));
+        match target {
+            GL::ARRAY_BUFFER => s.array_buffer = buffer,
+            GL::ELEMENT_ARRAY_BUFFER => s.element_buffer = buffer,
+            _ => (),
+        }
+    }
+
+    fn buffer_data(&self, target: u32, data: &[u8], usage: u32) {
+        self.record(Call::BufferData { target, size: data.len(), usage });
+    }
+
+    fn buffer_sub_data(&self, target: u32, offset: i32, data: &[u8]) {
+        self.record(Call::BufferSubData { target, offset, size: data.len() });
+    }
+
+    fn delete_buffer(&self, buffer: BufferId) {
+        let mut s = self.record(Call::DeleteBuffer(buffer));
+        s.buffers.remove(&buffer);
+        if s.array_buffer == Some(buffer) {
+            s.array_buffer = None;
+        }
+        if s.element_buffer == Some(buffer) {
+            s.element_buffer = None;
+        }
+    }
+
+    fn enable_vertex_attrib_array(&self, index: u32) {
+        self.record(Call::EnableVertexAttribArray(index)).attrib_arrays.insert(index);
+    }
+
+    fn disable_vertex_attrib_array(&self, index: u32) {
+        self.record(Call::DisableVertexAttribArray(index)).attrib_arrays.remove(&index);
+    }
+
+    fn vertex_attrib_pointer(&self, index: u32, size: i32, data_type: u32, normalized: bool, stride: i32, offset: i32) {
+        self.record(Call::VertexAttribPointer { index, size, data_type, normalized, stride, offset });
+    }
+
+    fn draw_elements(&self, mode: u32, count: i32, data_type: u32, offset: i32) {
+        self.record(Call::DrawElements { mode, count, data_type, offset });
+    }
+
+    fn draw_arrays(&self, mode: u32, first: i32, count: i32) {
+        self.record(Call::DrawArrays { mode, first, count });
+    }
+
+    fn vertex_attrib_divisor(&self, index: u32, divisor: u32) {
+        let mut s = self.record(Call::VertexAttribDivisor { index, divisor });
+        if divisor == 0 {
+            s.divisors.remove(&index);
+        } else {
+            s.divisors.insert(index, divisor);
+        }
+    }
+
+    fn draw_elements_instanced(&self, mode: u32, count: i32, data_type: u32, offset: i32, instances: i32) {
+        self.record(Call::DrawElementsInstanced { mode, count, data_type, offset, instances });
+    }
+
+    fn draw_arrays_instanced(&self, mode: u32, first: i32, count: i32, instances: i32) {
+        self.record(Call::DrawArraysInstanced { mode, first, count, instances });
+    }
+
+    fn create_texture(&self) -> Option<TextureId> {
+        let mut s = self.0.borrow_mut();
+        let id = TextureId(s.id());
+        s.textures.insert(id);
+        s.calls.push(Call::CreateTexture(id));
+        Some(id)
+    }
+
+    fn active_texture(&self, unit: u32) {
+        let mut s = self.record(Call::ActiveTexture(unit));
+        match unit.checked_sub(GL::TEXTURE0) {
+            Some(index) => s.active_unit = index,
+            None => {
+                s.error.get_or_insert(GL::INVALID_ENUM);
+            }
+        }
+    }
+
+    fn bind_texture(&self, target: u32, texture: Option<TextureId>) {
+        let mut s = self.record(Call::BindTexture(target, texture));
+        let unit = s.active_unit;
+        match texture {
+            Some(t) => s.units.insert(unit, t),
+            None => s.units.remove(&unit),
+        };
+    }
+
+    fn tex_image_2d(
+        &self,
+        target: u32,
+        level: i32,
+        internal_format: i32,
+        width: i32,
+        height: i32,
+        format: u32,
+        data_type: u32,
+        data: Option<&[u8]>,
+    ) -> Result<(), String> {
+        self.record(Call::TexImage2D {
+            target,
+            level,
+            internal_format,
+            width,
+            height,
+            format,
+            data_type,
+            size: data.map(|d| d.len()),
+        });
+        Ok(())
+    }
+
+    fn tex_parameteri(&self, target: u32, pname: u32, param: i32) {
+        self.record(Call::TexParameter(target, pname, param));
+    }
+
+    fn delete_texture(&self, texture: TextureId) {
+        let mut s = self.record(Call::DeleteTexture(texture));
+        s.textures.remove(&texture);
+        s.units.retain(|_, t| *t != texture);
+    }
+
+    fn create_framebuffer(&self) -> Option<FramebufferId> {
+        let mut s = self.0.borrow_mut();
+        let id = FramebufferId(s.id());
+        s.framebuffers.insert(id);
+        s.calls.push(Call::CreateFramebuffer(id));
+        Some(id)
+    }
+
+    fn bind_framebuffer(&self, target: u32, framebuffer: Option<FramebufferId>) {
+        self.record(Call::BindFramebuffer(target, framebuffer)).framebuffer = framebuffer;
+    }
+
+    fn framebuffer_texture_2d(&self, target: u32, attachment: u32, tex_target: u32, texture: Option<TextureId>, level: i32) {
+        self.record(Call::FramebufferTexture2D { target, attachment, tex_target, texture, level });
+    }
+
+    fn check_framebuffer_status(&self, target: u32) -> u32 {
+        let s = self.record(Call::CheckFramebufferStatus(target));
+        s.framebuffer_status.unwrap_or(GL::FRAMEBUFFER_COMPLETE)
+    }
+
+    fn delete_framebuffer(&self, framebuffer: FramebufferId) {
+        let mut s = self.record(Call::DeleteFramebuffer(framebuffer));
+        s.framebuffers.remove(&framebuffer);
+        if s.framebuffer == Some(framebuffer) {
+            s.framebuffer = None;
+        }
+    }
+
+    fn create_vertex_array(&self) -> Option<VertexArrayId> {
+        let mut s = self.0.borrow_mut();
+        let id = VertexArrayId(s.id());
+        s.vertex_arrays.insert(id, MockVertexArray::default());
+        s.calls.push(Call::CreateVertexArray(id));
+        Some(id)
+    }
+
+    fn bind_vertex_array(&self, vertex_array: Option<VertexArrayId>) {
+        self.record(Call::BindVertexArray(vertex_array)).switch_vertex_array(vertex_array);
+    }
+
+    fn delete_vertex_array(&self, vertex_array: VertexArrayId) {
+        let mut s = self.record(Call::DeleteVertexArray(vertex_array));
+        if s.vertex_array == Some(vertex_array) {
+            s.switch_vertex_array(None);
+        }
+        s.vertex_arrays.remove(&vertex_array);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn texture_unit_below_texture0_is_invalid_enum() {
+        let mock = MockBackend::new();
+
+        mock.active_texture(GL::TEXTURE0 + 3);
+        assert_eq!(mock.get_error(), GL::NO_ERROR);
+
+        mock.active_texture(0);
+        assert_eq!(mock.get_error(), GL::INVALID_ENUM);
+        assert_eq!(mock.get_error(), GL::NO_ERROR);
+
+        let texture = mock.create_texture();
+        mock.bind_texture(GL::TEXTURE_2D, texture);
+        assert_eq!(mock.texture_unit(3), texture);
+    }
+}
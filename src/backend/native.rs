@@ -0,0 +1,504 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use glow::HasContext;
+
+use crate::GL;
+use super::*;
+
+const RGBA32F: i32 = 0x8814;
+const RGB32F: i32 = 0x8815;
+
+/// Backend running on top of native OpenGL / GLES2 context through `glow`.
+///
+/// Context creation is left to the caller, any windowed, OSMesa or surfaceless EGL context works
+/// as long as it accepts GLSL ES 1.0 shaders (GLES2+ or desktop GL with `ARB_ES2_compatibility`).
+pub struct NativeBackend<C: HasContext = glow::Context> {
+    gl: C,
+    buffers: Objects<C::Buffer>,
+    shaders: Objects<C::Shader>,
+    programs: Objects<C::Program>,
+    textures: Objects<C::Texture>,
+    framebuffers: Objects<C::Framebuffer>,
+    vertex_arrays: Objects<C::VertexArray>,
+    locations: Objects<C::UniformLocation>,
+    location_ids: RefCell<HashMap<(u32, String), u32>>,
+}
+
+impl<C: HasContext> NativeBackend<C> {
+    /// # Safety
+    ///
+    /// `gl` has to stay current on the calling thread for as long as the backend is in use,
+    /// every call is forwarded to the driver as is.
+    pub unsafe fn new(gl: C) -> Self {
+        Self {
+            gl,
+            buffers: Objects::new(),
+            shaders: Objects::new(),
+            programs: Objects::new(),
+            textures: Objects::new(),
+            framebuffers: Objects::new(),
+            vertex_arrays: Objects::new(),
+            locations: Objects::new(),
+            location_ids: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn context(&self) -> &C {
+        &self.gl
+    }
+
+    /// Desktop GL 3+ or GLES 3+, desktop 2.x only has these features through extensions.
+    fn is_gl3(&self) -> bool {
+        self.gl.version().major >= 3
+    }
+}
+
+impl<C: HasContext> GlBackend for NativeBackend<C> {
+    /// WebGL extension names are translated to their native counterparts,
+    /// extensions promoted to core in desktop GL 3 / GLES 3 are always reported as available.
+    fn get_extension(&self, name: &str) -> bool {
+        let v = self.gl.version();
+        let core = match name {
+            "WEBGL_depth_texture" | "OES_texture_float" | "OES_element_index_uint" | "OES_vertex_array_object" => {
+                self.is_gl3()
+            }
+            "ANGLE_instanced_arrays" => v.major >= 3 && (v.is_embedded || v.major > 3 || v.minor >= 3),
+            _ => false,
+        };
+        let aliases: &[&str] = match name {
+            "WEBGL_depth_texture" => &["GL_OES_depth_texture", "GL_ARB_depth_texture"],
+            "ANGLE_instanced_arrays" => &["GL_ANGLE_instanced_arrays", "GL_ARB_instanced_arrays", "GL_EXT_instanced_arrays"],
+            "OES_vertex_array_object" => &["GL_OES_vertex_array_object", "GL_ARB_vertex_array_object"],
+            _ => &[],
+        };
+        let extensions = self.gl.supported_extensions();
+
+        core || extensions.contains(&format!("GL_{}", name)) || aliases.iter().any(|a| extensions.contains(*a))
+    }
+
+    fn is_context_lost(&self) -> bool {
+        false
+    }
+
+    fn enable(&self, cap: u32) {
+        unsafe { self.gl.enable(cap) }
+    }
+
+    fn disable(&self, cap: u32) {
+        unsafe { self.gl.disable(cap) }
+    }
+
+    fn viewport(&self, x: i32, y: i32, width: i32, height: i32) {
+        unsafe { self.gl.viewport(x, y, width, height) }
+    }
+
+    fn clear_color(&self, r: f32, g: f32, b: f32, a: f32) {
+        unsafe { self.gl.clear_color(r, g, b, a) }
+    }
+
+    fn clear_depth(&self, depth: f32) {
+        unsafe { self.gl.clear_depth_f32(depth) }
+    }
+
+    fn clear_stencil(&self, stencil: i32) {
+        unsafe { self.gl.clear_stencil(stencil) }
+    }
+
+    fn clear(&self, mask: u32) {
+        unsafe { self.gl.clear(mask) }
+    }
+
+    fn blend_equation_separate(&self, mode_rgb: u32, mode_alpha: u32) {
+        unsafe { self.gl.blend_equation_separate(mode_rgb, mode_alpha) }
+    }
+
+    fn blend_func_separate(&self, src_rgb: u32, dst_rgb: u32, src_alpha: u32, dst_alpha: u32) {
+        unsafe { self.gl.blend_func_separate(src_rgb, dst_rgb, src_alpha, dst_alpha) }
+    }
+
+    fn blend_color(&self, r: f32, g: f32, b: f32, a: f32) {
+        unsafe { self.gl.blend_color(r, g, b, a) }
+    }
+
+    fn depth_func(&self, func: u32) {
+        unsafe { self.gl.depth_func(func) }
+    }
+
+    fn depth_mask(&self, flag: bool) {
+        unsafe { self.gl.depth_mask(flag) }
+    }
+
+    fn cull_face(&self, mode: u32) {
+        unsafe { self.gl.cull_face(mode) }
+    }
+
+    fn front_face(&self, mode: u32) {
+        unsafe { self.gl.front_face(mode) }
+    }
+
+    fn color_mask(&self, r: bool, g: bool, b: bool, a: bool) {
+        unsafe { self.gl.color_mask(r, g, b, a) }
+    }
+
+    fn polygon_offset(&self, factor: f32, units: f32) {
+        unsafe { self.gl.polygon_offset(factor, units) }
+    }
+
+    fn stencil_func_separate(&self, face: u32, func: u32, reference: i32, mask: u32) {
+        unsafe { self.gl.stencil_func_separate(face, func, reference, mask) }
+    }
+
+    fn stencil_op_separate(&self, face: u32, fail: u32, depth_fail: u32, pass: u32) {
+        unsafe { self.gl.stencil_op_separate(face, fail, depth_fail, pass) }
+    }
+
+    fn stencil_mask_separate(&self, face: u32, mask: u32) {
+        unsafe { self.gl.stencil_mask_separate(face, mask) }
+    }
+
+    fn scissor(&self, x: i32, y: i32, width: i32, height: i32) {
+        unsafe { self.gl.scissor(x, y, width, height) }
+    }
+
+    fn create_shader(&self, shader_type: u32) -> Option<ShaderId> {
+        let shader = unsafe { self.gl.create_shader(shader_type) }.ok()?;
+        Some(ShaderId(self.shaders.insert(shader)))
+    }
+
+    fn shader_source(&self, shader: ShaderId, source: &str) {
+        if let Some(s) = self.shaders.get(shader.0) {
+            unsafe { self.gl.shader_source(*s, source) }
+        }
+    }
+
+    fn compile_shader(&self, shader: ShaderId) {
+        if let Some(s) = self.shaders.get(shader.0) {
+            unsafe { self.gl.compile_shader(*s) }
+        }
+    }
+
+    fn get_shader_parameter(&self, shader: ShaderId, pname: u32) -> i32 {
+        match (self.shaders.get(shader.0), pname) {
+            (Some(s), GL::COMPILE_STATUS) => unsafe { self.gl.get_shader_compile_status(*s) as i32 },
+            _ => 0,
+        }
+    }
+
+    fn get_shader_info_log(&self, shader: ShaderId) -> Option<String> {
+        self.shaders.get(shader.0).map(|s| unsafe { self.gl.get_shader_info_log(*s) })
+    }
+
+    fn delete_shader(&self, shader: ShaderId) {
+        if let Some(s) = self.shaders.remove(shader.0) {
+            unsafe { self.gl.delete_shader(s) }
+        }
+    }
+
+    fn create_program(&self) -> Option<ProgramId> {
+        let program = unsafe { self.gl.create_program() }.ok()?;
+        Some(ProgramId(self.programs.insert(program)))
+    }
+
+    fn attach_shader(&self, program: ProgramId, shader: ShaderId) {
+        if let (Some(p), Some(s)) = (self.programs.get(program.0), self.shaders.get(shader.0)) {
+            unsafe { self.gl.attach_shader(*p, *s) }
+        }
+    }
+
+    fn detach_shader(&self, program: ProgramId, shader: ShaderId) {
+        if let (Some(p), Some(s)) = (self.programs.get(program.0), self.shaders.get(shader.0)) {
+            unsafe { self.gl.detach_shader(*p, *s) }
+        }
+    }
+
+    fn link_program(&self, program: ProgramId) {
+        if let Some(p) = self.programs.get(program.0) {
+            unsafe { self.gl.link_program(*p) }
+        }
+    }
+
+    fn validate_program(&self, program: ProgramId) {
+        if let Some(p) = self.programs.get(program.0) {
+            unsafe { self.gl.validate_program(*p) }
+        }
+    }
+
+    fn get_program_parameter(&self, program: ProgramId, pname: u32) -> i32 {
+        self.programs
+            .get(program.0)
+            .map(|p| unsafe { self.gl.get_program_parameter_i32(*p, pname) })
+            .unwrap_or(0)
+    }
+
+    fn get_program_info_log(&self, program: ProgramId) -> Option<String> {
+        self.programs.get(program.0).map(|p| unsafe { self.gl.get_program_info_log(*p) })
+    }
+
+    fn use_program(&self, program: Option<ProgramId>) {
+        let p = program.and_then(|p| self.programs.get(p.0)).map(|p| *p);
+        unsafe { self.gl.use_program(p) }
+    }
+
+    fn delete_program(&self, program: ProgramId) {
+        self.location_ids.borrow_mut().retain(|(p, _), loc| {
+            let keep = *p != program.0;
+            if !keep {
+                self.locations.remove(*loc);
+            }
+            keep
+        });
+        if let Some(p) = self.programs.remove(program.0) {
+            unsafe { self.gl.delete_program(p) }
+        }
+    }
+
+    fn get_attrib_location(&self, program: ProgramId, name: &str) -> i32 {
+        self.programs
+            .get(program.0)
+            .and_then(|p| unsafe { self.gl.get_attrib_location(*p, name) })
+            .map(|idx| idx as i32)
+            .unwrap_or(-1)
+    }
+
+    fn get_uniform_location(&self, program: ProgramId, name: &str) -> Option<UniformLocation> {
+        let key = (program.0, name.to_string());
+        if let Some(&id) = self.location_ids.borrow().get(&key) {
+            return Some(UniformLocation(id));
+        }
+        let p = *self.programs.get(program.0)?;
+        let loc = unsafe { self.gl.get_uniform_location(p, name) }?;
+        let id = self.locations.insert(loc);
+        self.location_ids.borrow_mut().insert(key, id);
+
+        Some(UniformLocation(id))
+    }
+
+    fn get_active_attrib(&self, program: ProgramId, index: u32) -> Option<ActiveInfo> {
+        let p = *self.programs.get(program.0)?;
+        let info = unsafe { self.gl.get_active_attribute(p, index) }?;
+        Some(ActiveInfo { name: info.name, gl_type: info.atype, size: info.size })
+    }
+
+    fn get_active_uniform(&self, program: ProgramId, index: u32) -> Option<ActiveInfo> {
+        let p = *self.programs.get(program.0)?;
+        let info = unsafe { self.gl.get_active_uniform(p, index) }?;
+        Some(ActiveInfo { name: info.name, gl_type: info.utype, size: info.size })
+    }
+
+    fn uniform1i(&self, location: UniformLocation, v: i32) {
+        unsafe { self.gl.uniform_1_i32(self.locations.get(location.0).as_deref(), v) }
+    }
+
+    fn uniform1f(&self, location: UniformLocation, v: f32) {
+        unsafe { self.gl.uniform_1_f32(self.locations.get(location.0).as_deref(), v) }
+    }
+
+    fn uniform1iv(&self, location: UniformLocation, v: &[i32]) {
+        unsafe { self.gl.uniform_1_i32_slice(self.locations.get(location.0).as_deref(), v) }
+    }
+
+    fn uniform1fv(&self, location: UniformLocation, v: &[f32]) {
+        unsafe { self.gl.uniform_1_f32_slice(self.locations.get(location.0).as_deref(), v) }
+    }
+
+    fn uniform2fv(&self, location: UniformLocation, v: &[f32]) {
+        unsafe { self.gl.uniform_2_f32_slice(self.locations.get(location.0).as_deref(), v) }
+    }
+
+    fn uniform3fv(&self, location: UniformLocation, v: &[f32]) {
+        unsafe { self.gl.uniform_3_f32_slice(self.locations.get(location.0).as_deref(), v) }
+    }
+
+    fn uniform4fv(&self, location: UniformLocation, v: &[f32]) {
+        unsafe { self.gl.uniform_4_f32_slice(self.locations.get(location.0).as_deref(), v) }
+    }
+
+    fn uniform2iv(&self, location: UniformLocation, v: &[i32]) {
+        unsafe { self.gl.uniform_2_i32_slice(self.locations.get(location.0).as_deref(), v) }
+    }
+
+    fn uniform3iv(&self, location: UniformLocation, v: &[i32]) {
+        unsafe { self.gl.uniform_3_i32_slice(self.locations.get(location.0).as_deref(), v) }
+    }
+
+    fn uniform4iv(&self, location: UniformLocation, v: &[i32]) {
+        unsafe { self.gl.uniform_4_i32_slice(self.locations.get(location.0).as_deref(), v) }
+    }
+
+    fn uniform_matrix2fv(&self, location: UniformLocation, transpose: bool, v: &[f32]) {
+        unsafe { self.gl.uniform_matrix_2_f32_slice(self.locations.get(location.0).as_deref(), transpose, v) }
+    }
+
+    fn uniform_matrix3fv(&self, location: UniformLocation, transpose: bool, v: &[f32]) {
+        unsafe { self.gl.uniform_matrix_3_f32_slice(self.locations.get(location.0).as_deref(), transpose, v) }
+    }
+
+    fn uniform_matrix4fv(&self, location: UniformLocation, transpose: bool, v: &[f32]) {
+        unsafe { self.gl.uniform_matrix_4_f32_slice(self.locations.get(location.0).as_deref(), transpose, v) }
+    }
+
+    fn create_buffer(&self) -> Option<BufferId> {
+        let buffer = unsafe { self.gl.create_buffer() }.ok()?;
+        Some(BufferId(self.buffers.insert(buffer)))
+    }
+
+    fn bind_buffer(&self, target: u32, buffer: Option<BufferId>) {
+        let b = buffer.and_then(|b| self.buffers.get(b.0)).map(|b| *b);
+        unsafe { self.gl.bind_buffer(target, b) }
+    }
+
+    fn buffer_data(&self, target: u32, data: &[u8], usage: u32) {
+        unsafe { self.gl.buffer_data_u8_slice(target, data, usage) }
+    }
+
+    fn buffer_sub_data(&self, target: u32, offset: i32, data: &[u8]) {
+        unsafe { self.gl.buffer_sub_data_u8_slice(target, offset, data) }
+    }
+
+    fn delete_buffer(&self, buffer: BufferId) {
+        if let Some(b) = self.buffers.remove(buffer.0) {
+            unsafe { self.gl.delete_buffer(b) }
+        }
+    }
+
+    fn enable_vertex_attrib_array(&self, index: u32) {
+        unsafe { self.gl.enable_vertex_attrib_array(index) }
+    }
+
+    fn disable_vertex_attrib_array(&self, index: u32) {
+        unsafe { self.gl.disable_vertex_attrib_array(index) }
+    }
+
+    fn vertex_attrib_pointer(&self, index: u32, size: i32, data_type: u32, normalized: bool, stride: i32, offset: i32) {
+        unsafe { self.gl.vertex_attrib_pointer_f32(index, size, data_type, normalized, stride, offset) }
+    }
+
+    fn draw_elements(&self, mode: u32, count: i32, data_type: u32, offset: i32) {
+        unsafe { self.gl.draw_elements(mode, count, data_type, offset) }
+    }
+
+    fn draw_arrays(&self, mode: u32, first: i32, count: i32) {
+        unsafe { self.gl.draw_arrays(mode, first, count) }
+    }
+
+    fn vertex_attrib_divisor(&self, index: u32, divisor: u32) {
+        unsafe { self.gl.vertex_attrib_divisor(index, divisor) }
+    }
+
+    fn draw_elements_instanced(&self, mode: u32, count: i32, data_type: u32, offset: i32, instances: i32) {
+        unsafe { self.gl.draw_elements_instanced(mode, count, data_type, offset, instances) }
+    }
+
+    fn draw_arrays_instanced(&self, mode: u32, first: i32, count: i32, instances: i32) {
+        unsafe { self.gl.draw_arrays_instanced(mode, first, count, instances) }
+    }
+
+    fn create_texture(&self) -> Option<TextureId> {
+        let texture = unsafe { self.gl.create_texture() }.ok()?;
+        Some(TextureId(self.textures.insert(texture)))
+    }
+
+    fn active_texture(&self, unit: u32) {
+        unsafe { self.gl.active_texture(unit) }
+    }
+
+    fn bind_texture(&self, target: u32, texture: Option<TextureId>) {
+        let t = texture.and_then(|t| self.textures.get(t.0)).map(|t| *t);
+        unsafe { self.gl.bind_texture(target, t) }
+    }
+
+    fn tex_image_2d(
+        &self,
+        target: u32,
+        level: i32,
+        internal_format: i32,
+        width: i32,
+        height: i32,
+        format: u32,
+        data_type: u32,
+        data: Option<&[u8]>,
+    ) -> Result<(), String> {
+        // unsized float formats are WebGL / GLES2 only, GL3 needs explicit float storage
+        let internal_format = match (data_type, internal_format as u32) {
+            (GL::FLOAT, GL::RGBA) if self.is_gl3() => RGBA32F,
+            (GL::FLOAT, GL::RGB) if self.is_gl3() => RGB32F,
+            _ => internal_format,
+        };
+        unsafe {
+            // errors left by earlier calls would be reported as upload failure, each flag is cleared
+            // on read and there is only a handful of them, so the loop is bounded in case of lost context
+            for _ in 0..8 {
+                if self.gl.get_error() == glow::NO_ERROR {
+                    break;
+                }
+            }
+            self.gl.tex_image_2d(
+                target,
+                level,
+                internal_format,
+                width,
+                height,
+                0,
+                format,
+                data_type,
+                glow::PixelUnpackData::Slice(data),
+            );
+            match self.gl.get_error() {
+                glow::NO_ERROR => Ok(()),
+                e => Err(format!("GL error {:#x}", e)),
+            }
+        }
+    }
+
+    fn tex_parameteri(&self, target: u32, pname: u32, param: i32) {
+        unsafe { self.gl.tex_parameter_i32(target, pname, param) }
+    }
+
+    fn delete_texture(&self, texture: TextureId) {
+        if let Some(t) = self.textures.remove(texture.0) {
+            unsafe { self.gl.delete_texture(t) }
+        }
+    }
+
+    fn create_framebuffer(&self) -> Option<FramebufferId> {
+        let framebuffer = unsafe { self.gl.create_framebuffer() }.ok()?;
+        Some(FramebufferId(self.framebuffers.insert(framebuffer)))
+    }
+
+    fn bind_framebuffer(&self, target: u32, framebuffer: Option<FramebufferId>) {
+        let f = framebuffer.and_then(|f| self.framebuffers.get(f.0)).map(|f| *f);
+        unsafe { self.gl.bind_framebuffer(target, f) }
+    }
+
+    fn framebuffer_texture_2d(&self, target: u32, attachment: u32, tex_target: u32, texture: Option<TextureId>, level: i32) {
+        let t = texture.and_then(|t| self.textures.get(t.0)).map(|t| *t);
+        unsafe { self.gl.framebuffer_texture_2d(target, attachment, tex_target, t, level) }
+    }
+
+    fn check_framebuffer_status(&self, target: u32) -> u32 {
+        unsafe { self.gl.check_framebuffer_status(target) }
+    }
+
+    fn delete_framebuffer(&self, framebuffer: FramebufferId) {
+        if let Some(f) = self.framebuffers.remove(framebuffer.0) {
+            unsafe { self.gl.delete_framebuffer(f) }
+        }
+    }
+
+    fn create_vertex_array(&self) -> Option<VertexArrayId> {
+        let vertex_array = unsafe { self.gl.create_vertex_array() }.ok()?;
+        Some(VertexArrayId(self.vertex_arrays.insert(vertex_array)))
+    }
+
+    fn bind_vertex_array(&self, vertex_array: Option<VertexArrayId>) {
+        let v = vertex_array.and_then(|v| self.vertex_arrays.get(v.0)).map(|v| *v);
+        unsafe { self.gl.bind_vertex_array(v) }
+    }
+
+    fn delete_vertex_array(&self, vertex_array: VertexArrayId) {
+        if let Some(v) = self.vertex_arrays.remove(vertex_array.0) {
+            unsafe { self.gl.delete_vertex_array(v) }
+        }
+    }
+}
@@ -0,0 +1,1641 @@
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use std::rc::Rc;
+
+use crate::GL;
+use super::*;
+
+/// Vertex shader stand-in, returns clip space position and varyings for a single vertex.
+pub type VertexShader = Rc<dyn Fn(&SoftUniforms, &Attributes) -> ([f32; 4], Vec<f32>)>;
+
+/// Fragment shader stand-in, receives interpolated varyings, `None` discards the fragment.
+pub type FragmentShader = Rc<dyn Fn(&SoftUniforms, &[f32]) -> Option<[f32; 4]>>;
+
+/// Uniform values of the program being drawn, along with textures bound to sampler units.
+pub struct SoftUniforms<'a> {
+    values: &'a HashMap<String, UniformValue>,
+    units: &'a HashMap<u32, TextureId>,
+    textures: &'a HashMap<TextureId, SoftTexture>,
+}
+
+impl<'a> SoftUniforms<'a> {
+    pub fn get(&self, name: &str) -> Option<&UniformValue> {
+        self.values.get(name)
+    }
+
+    fn floats(&self, name: &str) -> &[f32] {
+        match self.values.get(name) {
+            Some(UniformValue::Float(v)) => std::slice::from_ref(v),
+            Some(UniformValue::FloatArray(v))
+            | Some(UniformValue::FloatVec2(v))
+            | Some(UniformValue::FloatVec3(v))
+            | Some(UniformValue::FloatVec4(v))
+            | Some(UniformValue::FloatMat2 { data: v, .. })
+            | Some(UniformValue::FloatMat3 { data: v, .. })
+            | Some(UniformValue::FloatMat4 { data: v, .. }) => v,
+            _ => &[],
+        }
+    }
+
+    fn ints(&self, name: &str) -> &[i32] {
+        match self.values.get(name) {
+            Some(UniformValue::Int(v)) => std::slice::from_ref(v),
+            Some(UniformValue::IntArray(v))
+            | Some(UniformValue::IntVec2(v))
+            | Some(UniformValue::IntVec3(v))
+            | Some(UniformValue::IntVec4(v)) => v,
+            _ => &[],
+        }
+    }
+
+    pub fn float(&self, name: &str) -> f32 {
+        self.floats(name).first().copied().unwrap_or(0.)
+    }
+
+    pub fn int(&self, name: &str) -> i32 {
+        self.ints(name).first().copied().unwrap_or(0)
+    }
+
+    /// Booleans are uploaded as integers, same as in GL.
+    pub fn bool(&self, name: &str) -> bool {
+        self.int(name) != 0
+    }
+
+    pub fn ivec2(&self, name: &str) -> [i32; 2] {
+        let mut r = [0; 2];
+        r.iter_mut().zip(self.ints(name)).for_each(|(r, v)| *r = *v);
+        r
+    }
+
+    pub fn ivec3(&self, name: &str) -> [i32; 3] {
+        let mut r = [0; 3];
+        r.iter_mut().zip(self.ints(name)).for_each(|(r, v)| *r = *v);
+        r
+    }
+
+    pub fn ivec4(&self, name: &str) -> [i32; 4] {
+        let mut r = [0; 4];
+        r.iter_mut().zip(self.ints(name)).for_each(|(r, v)| *r = *v);
+        r
+    }
+
+    pub fn vec2(&self, name: &str) -> [f32; 2] {
+        let mut r = [0.; 2];
+        r.iter_mut().zip(self.floats(name)).for_each(|(r, v)| *r = *v);
+        r
+    }
+
+    pub fn vec3(&self, name: &str) -> [f32; 3] {
+        let mut r = [0.; 3];
+        r.iter_mut().zip(self.floats(name)).for_each(|(r, v)| *r = *v);
+        r
+    }
+
+    pub fn vec4(&self, name: &str) -> [f32; 4] {
+        let mut r = [0.; 4];
+        r.iter_mut().zip(self.floats(name)).for_each(|(r, v)| *r = *v);
+        r
+    }
+
+    pub fn mat2(&self, name: &str) -> [f32; 4] {
+        let mut r = [0.; 4];
+        r.iter_mut().zip(self.floats(name)).for_each(|(r, v)| *r = *v);
+        r
+    }
+
+    pub fn mat3(&self, name: &str) -> [f32; 9] {
+        let mut r = [0.; 9];
+        r.iter_mut().zip(self.floats(name)).for_each(|(r, v)| *r = *v);
+        r
+    }
+
+    /// Column-major matrix, same layout as uploaded through `UniformData::Matrix4`.
+    pub fn mat4(&self, name: &str) -> [f32; 16] {
+        let mut r = [0.; 16];
+        r.iter_mut().zip(self.floats(name)).for_each(|(r, v)| *r = *v);
+        r
+    }
+
+    /// Equivalent of GLSL `texture2D(sampler, uv)`, unbound samplers read as opaque black.
+    pub fn texture2d(&self, sampler: &str, uv: [f32; 2]) -> [f32; 4] {
+        self.units
+            .get(&(self.int(sampler) as u32))
+            .and_then(|t| self.textures.get(t))
+            .map(|t| t.sample(uv))
+            .unwrap_or([0., 0., 0., 1.])
+    }
+}
+
+/// Attribute values of the vertex being shaded, padded to `vec4` with `(0, 0, 0, 1)` like in GL.
+pub struct Attributes<'a> {
+    names: &'a [String],
+    values: &'a [[f32; 4]],
+}
+
+impl<'a> Attributes<'a> {
+    /// Column `column` of a matrix attribute.
+    pub fn column(&self, name: &str, column: usize) -> [f32; 4] {
+        self.names
+            .iter()
+            .position(|n| n == name)
+            .and_then(|i| self.values.get(i + column))
+            .copied()
+            .unwrap_or([0., 0., 0., 1.])
+    }
+
+    pub fn get(&self, name: &str) -> [f32; 4] {
+        self.column(name, 0)
+    }
+}
+
+/// RGBA8 image with rows stored top to bottom, ready to be compared against golden images.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+impl Image {
+    /// Largest per channel difference, `None` if dimensions differ.
+    pub fn max_difference(&self, other: &Image) -> Option<u8> {
+        if self.width != other.width || self.height != other.height {
+            return None;
+        }
+        Some(
+            self.data
+                .iter()
+                .zip(other.data.iter())
+                .map(|(a, b)| (*a as i32 - *b as i32).unsigned_abs() as u8)
+                .max()
+                .unwrap_or(0),
+        )
+    }
+
+    #[cfg(feature = "png")]
+    pub fn load_png<P: AsRef<std::path::Path>>(path: P) -> Result<Self, String> {
+        let file = std::fs::File::open(path).map_err(|e| format!("Failed to open image {}", e))?;
+        let mut decoder = png::Decoder::new(std::io::BufReader::new(file));
+        decoder.set_transformations(png::Transformations::normalize_to_color8() | png::Transformations::ALPHA);
+        let mut reader = decoder.read_info().map_err(|e| format!("Failed to decode image {}", e))?;
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data).map_err(|e| format!("Failed to decode image {}", e))?;
+        data.truncate(info.buffer_size());
+
+        let data = match info.color_type {
+            png::ColorType::Rgba => data,
+            png::ColorType::Rgb => data.chunks(3).flat_map(|c| [c[0], c[1], c[2], 255]).collect(),
+            png::ColorType::GrayscaleAlpha => data.chunks(2).flat_map(|c| [c[0], c[0], c[0], c[1]]).collect(),
+            _ => data.iter().flat_map(|c| [*c, *c, *c, 255]).collect(),
+        };
+
+        Ok(Self { width: info.width, height: info.height, data })
+    }
+
+    #[cfg(feature = "png")]
+    pub fn save_png<P: AsRef<std::path::Path>>(&self, path: P) -> Result<(), String> {
+        let file = std::fs::File::create(path).map_err(|e| format!("Failed to create image {}", e))?;
+        let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut w| w.write_image_data(&self.data))
+            .map_err(|e| format!("Failed to encode image {}", e))
+    }
+}
+
+struct SoftTexture {
+    width: u32,
+    height: u32,
+    data_type: u32,
+    // rows bottom to top as in GL
+    texels: Vec<[f32; 4]>,
+    min_filter: u32,
+    mag_filter: u32,
+    wrap_s: u32,
+    wrap_t: u32,
+}
+
+fn wrap(coord: i32, size: u32, mode: u32) -> u32 {
+    let size = size as i32;
+    let c = match mode {
+        GL::REPEAT => coord.rem_euclid(size),
+        GL::MIRRORED_REPEAT => {
+            let period = coord.rem_euclid(2 * size);
+            if period < size { period } else { 2 * size - 1 - period }
+        }
+        _ => coord.clamp(0, size - 1),
+    };
+    c as u32
+}
+
+impl SoftTexture {
+    fn new(width: u32, height: u32, data_type: u32) -> Self {
+        Self {
+            width,
+            height,
+            data_type,
+            texels: vec![[0., 0., 0., 0.]; (width * height) as usize],
+            min_filter: GL::NEAREST_MIPMAP_LINEAR,
+            mag_filter: GL::LINEAR,
+            wrap_s: GL::REPEAT,
+            wrap_t: GL::REPEAT,
+        }
+    }
+
+    fn texel(&self, x: i32, y: i32) -> [f32; 4] {
+        let x = wrap(x, self.width, self.wrap_s);
+        let y = wrap(y, self.height, self.wrap_t);
+        self.texels[(y * self.width + x) as usize]
+    }
+
+    fn sample(&self, uv: [f32; 2]) -> [f32; 4] {
+        if self.texels.is_empty() {
+            return [0., 0., 0., 1.];
+        }
+        let x = uv[0] * self.width as f32 - 0.5;
+        let y = uv[1] * self.height as f32 - 0.5;
+        // no mipmaps, minification filter only decides between nearest and linear
+        let linear = matches!(self.mag_filter, GL::LINEAR)
+            && matches!(self.min_filter, GL::LINEAR | GL::LINEAR_MIPMAP_NEAREST | GL::LINEAR_MIPMAP_LINEAR);
+        if !linear {
+            return self.texel((x + 0.5).floor() as i32, (y + 0.5).floor() as i32);
+        }
+
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i32, y0 as i32);
+        let (a, b, c, d) = (
+            self.texel(x0, y0),
+            self.texel(x0 + 1, y0),
+            self.texel(x0, y0 + 1),
+            self.texel(x0 + 1, y0 + 1),
+        );
+        let mut r = [0.; 4];
+        for i in 0..4 {
+            let bottom = a[i] + (b[i] - a[i]) * fx;
+            let top = c[i] + (d[i] - c[i]) * fx;
+            r[i] = bottom + (top - bottom) * fy;
+        }
+        r
+    }
+
+    fn write(&mut self, idx: usize, color: [f32; 4]) {
+        self.texels[idx] = if self.data_type == GL::FLOAT {
+            color
+        } else {
+            [color[0].clamp(0., 1.), color[1].clamp(0., 1.), color[2].clamp(0., 1.), color[3].clamp(0., 1.)]
+        };
+    }
+
+    fn image(&self) -> Image {
+        let mut data = Vec::with_capacity((self.width * self.height * 4) as usize);
+        for row in self.texels.chunks(self.width.max(1) as usize).rev() {
+            for texel in row {
+                data.extend(texel.iter().map(|c| (c.clamp(0., 1.) * 255.).round() as u8));
+            }
+        }
+        Image { width: self.width, height: self.height, data }
+    }
+}
+
+fn decode_texels(format: u32, data_type: u32, data: &[u8]) -> Vec<[f32; 4]> {
+    let values = match data_type {
+        GL::FLOAT => data.chunks_exact(4).map(|c| f32::from_ne_bytes([c[0], c[1], c[2], c[3]])).collect::<Vec<_>>(),
+        GL::UNSIGNED_SHORT => data
+            .chunks_exact(2)
+            .map(|c| u16::from_ne_bytes([c[0], c[1]]) as f32 / u16::MAX as f32)
+            .collect(),
+        GL::UNSIGNED_INT => data
+            .chunks_exact(4)
+            .map(|c| (u32::from_ne_bytes([c[0], c[1], c[2], c[3]]) as f64 / u32::MAX as f64) as f32)
+            .collect(),
+        _ => data.iter().map(|v| *v as f32 / 255.).collect(),
+    };
+
+    match format {
+        GL::RGBA => values.chunks_exact(4).map(|c| [c[0], c[1], c[2], c[3]]).collect(),
+        GL::RGB => values.chunks_exact(3).map(|c| [c[0], c[1], c[2], 1.]).collect(),
+        GL::LUMINANCE_ALPHA => values.chunks_exact(2).map(|c| [c[0], c[0], c[0], c[1]]).collect(),
+        GL::ALPHA => values.iter().map(|a| [0., 0., 0., *a]).collect(),
+        _ => values.iter().map(|v| [*v, *v, *v, 1.]).collect(),
+    }
+}
+
+struct SoftShader {
+    shader_type: u32,
+    source: String,
+    compiled: bool,
+    log: Option<String>,
+}
+
+struct Linked {
+    vertex: VertexShader,
+    fragment: FragmentShader,
+    attributes: Vec<String>,
+    locations: HashMap<String, UniformLocation>,
+    values: HashMap<String, UniformValue>,
+    active_attributes: Vec<ActiveInfo>,
+    active_uniforms: Vec<ActiveInfo>,
+}
+
+#[derive(Default)]
+struct SoftProgram {
+    shaders: Vec<ShaderId>,
+    linked: Option<Linked>,
+    log: String,
+}
+
+#[derive(Clone, Copy)]
+struct Pointer {
+    buffer: BufferId,
+    size: i32,
+    data_type: u32,
+    normalized: bool,
+    stride: i32,
+    offset: i32,
+}
+
+/// Per-fragment operations and face culling, tests and blending are enabled through `capabilities`.
+#[derive(Clone, Copy)]
+struct FixedFunction {
+    blend_equation: [u32; 2],
+    blend_func: [u32; 4],
+    blend_color: [f32; 4],
+    depth_func: u32,
+    depth_mask: bool,
+    cull_face: u32,
+    front_face: u32,
+    color_mask: [bool; 4],
+    polygon_offset: [f32; 2],
+    scissor: [i32; 4],
+}
+
+impl FixedFunction {
+    fn new(width: u32, height: u32) -> Self {
+        Self {
+            blend_equation: [GL::FUNC_ADD, GL::FUNC_ADD],
+            blend_func: [GL::ONE, GL::ZERO, GL::ONE, GL::ZERO],
+            blend_color: [0., 0., 0., 0.],
+            depth_func: GL::LESS,
+            depth_mask: true,
+            cull_face: GL::BACK,
+            front_face: GL::CCW,
+            color_mask: [true; 4],
+            polygon_offset: [0., 0.],
+            scissor: [0, 0, width as i32, height as i32],
+        }
+    }
+}
+
+fn compare(func: u32, value: f32, stored: f32) -> bool {
+    match func {
+        GL::NEVER => false,
+        GL::LESS => value < stored,
+        GL::EQUAL => value == stored,
+        GL::LEQUAL => value <= stored,
+        GL::GREATER => value > stored,
+        GL::NOTEQUAL => value != stored,
+        GL::GEQUAL => value >= stored,
+        _ => true,
+    }
+}
+
+fn blend_factor(factor: u32, src: [f32; 4], dst: [f32; 4], constant: [f32; 4]) -> [f32; 4] {
+    let splat = |v: f32| [v, v, v, v];
+    let inverse = |c: [f32; 4]| c.map(|v| 1. - v);
+    match factor {
+        GL::ZERO => splat(0.),
+        GL::SRC_COLOR => src,
+        GL::ONE_MINUS_SRC_COLOR => inverse(src),
+        GL::DST_COLOR => dst,
+        GL::ONE_MINUS_DST_COLOR => inverse(dst),
+        GL::SRC_ALPHA => splat(src[3]),
+        GL::ONE_MINUS_SRC_ALPHA => splat(1. - src[3]),
+        GL::DST_ALPHA => splat(dst[3]),
+        GL::ONE_MINUS_DST_ALPHA => splat(1. - dst[3]),
+        GL::CONSTANT_COLOR => constant,
+        GL::ONE_MINUS_CONSTANT_COLOR => inverse(constant),
+        GL::CONSTANT_ALPHA => splat(constant[3]),
+        GL::ONE_MINUS_CONSTANT_ALPHA => splat(1. - constant[3]),
+        GL::SRC_ALPHA_SATURATE => {
+            let f = src[3].min(1. - dst[3]);
+            [f, f, f, 1.]
+        }
+        _ => splat(1.),
+    }
+}
+
+fn blend(f: &FixedFunction, src: [f32; 4], dst: [f32; 4]) -> [f32; 4] {
+    let [src_rgb, dst_rgb, src_alpha, dst_alpha] = f.blend_func;
+    let (s_rgb, d_rgb) = (blend_factor(src_rgb, src, dst, f.blend_color), blend_factor(dst_rgb, src, dst, f.blend_color));
+    let (s_a, d_a) = (blend_factor(src_alpha, src, dst, f.blend_color), blend_factor(dst_alpha, src, dst, f.blend_color));
+    let mut result = [0.; 4];
+    for (i, r) in result.iter_mut().enumerate() {
+        let (s, d, equation) = if i < 3 {
+            (src[i] * s_rgb[i], dst[i] * d_rgb[i], f.blend_equation[0])
+        } else {
+            (src[i] * s_a[i], dst[i] * d_a[i], f.blend_equation[1])
+        };
+        *r = match equation {
+            GL::FUNC_SUBTRACT => s - d,
+            GL::FUNC_REVERSE_SUBTRACT => d - s,
+            _ => s + d,
+        };
+    }
+    result
+}
+
+/// State captured by a vertex array object.
+#[derive(Default)]
+struct SoftVertexArray {
+    attrib_arrays: BTreeSet<u32>,
+    pointers: HashMap<u32, Pointer>,
+    divisors: HashMap<u32, u32>,
+    element_buffer: Option<BufferId>,
+}
+
+#[derive(Default)]
+struct Attachments {
+    color: Option<TextureId>,
+    depth: Option<TextureId>,
+}
+
+/// Uniform location points at `index`-th element of declaration `name`.
+struct SoftUniform {
+    program: ProgramId,
+    name: String,
+    gl_type: u32,
+    index: i32,
+    size: i32,
+}
+
+struct SoftState {
+    next_id: u32,
+    vertex_shaders: HashMap<String, VertexShader>,
+    fragment_shaders: HashMap<String, FragmentShader>,
+
+    shaders: HashMap<ShaderId, SoftShader>,
+    programs: HashMap<ProgramId, SoftProgram>,
+    uniform_names: HashMap<UniformLocation, SoftUniform>,
+    buffers: HashMap<BufferId, Vec<u8>>,
+    textures: HashMap<TextureId, SoftTexture>,
+    framebuffers: HashMap<FramebufferId, Attachments>,
+    vertex_arrays: HashMap<VertexArrayId, SoftVertexArray>,
+    default_vertex_array: SoftVertexArray,
+
+    screen: SoftTexture,
+    screen_depth: SoftTexture,
+
+    capabilities: BTreeSet<u32>,
+    fixed: FixedFunction,
+    viewport: [i32; 4],
+    clear_color: [f32; 4],
+    clear_depth: f32,
+    program: Option<ProgramId>,
+    framebuffer: Option<FramebufferId>,
+    vertex_array: Option<VertexArrayId>,
+    array_buffer: Option<BufferId>,
+    element_buffer: Option<BufferId>,
+    active_unit: u32,
+    units: HashMap<u32, TextureId>,
+    attrib_arrays: BTreeSet<u32>,
+    pointers: HashMap<u32, Pointer>,
+    divisors: HashMap<u32, u32>,
+}
+
+impl SoftState {
+    fn id(&mut self) -> u32 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn bound_texture(&mut self) -> Option<&mut SoftTexture> {
+        let id = self.units.get(&self.active_unit)?;
+        self.textures.get_mut(id)
+    }
+
+    /// Stores state of currently bound vertex array and loads the one of `next`.
+    fn switch_vertex_array(&mut self, next: Option<VertexArrayId>) {
+        let current = SoftVertexArray {
+            attrib_arrays: std::mem::take(&mut self.attrib_arrays),
+            pointers: std::mem::take(&mut self.pointers),
+            divisors: std::mem::take(&mut self.divisors),
+            element_buffer: self.element_buffer.take(),
+        };
+        match self.vertex_array {
+            Some(id) => {
+                if let Some(v) = self.vertex_arrays.get_mut(&id) {
+                    *v = current;
+                }
+            }
+            None => self.default_vertex_array = current,
+        }
+        let next_state = match next {
+            Some(id) => self.vertex_arrays.get_mut(&id).map(std::mem::take).unwrap_or_default(),
+            None => std::mem::take(&mut self.default_vertex_array),
+        };
+        self.attrib_arrays = next_state.attrib_arrays;
+        self.pointers = next_state.pointers;
+        self.divisors = next_state.divisors;
+        self.element_buffer = next_state.element_buffer;
+        self.vertex_array = next;
+    }
+}
+
+/// Render target pulled out of `SoftState` for the duration of a draw or clear.
+struct Surface {
+    color: Option<(Option<TextureId>, SoftTexture)>,
+    depth: Option<(Option<TextureId>, SoftTexture)>,
+}
+
+impl Surface {
+    fn take(s: &mut SoftState) -> Self {
+        match s.framebuffer {
+            None => Surface {
+                color: Some((None, std::mem::replace(&mut s.screen, SoftTexture::new(0, 0, GL::UNSIGNED_BYTE)))),
+                depth: Some((None, std::mem::replace(&mut s.screen_depth, SoftTexture::new(0, 0, GL::FLOAT)))),
+            },
+            Some(fb) => {
+                let (color, depth) = s
+                    .framebuffers
+                    .get(&fb)
+                    .map(|a| (a.color, a.depth))
+                    .unwrap_or((None, None));
+                Surface {
+                    color: color.and_then(|id| s.textures.remove(&id).map(|t| (Some(id), t))),
+                    depth: depth.and_then(|id| s.textures.remove(&id).map(|t| (Some(id), t))),
+                }
+            }
+        }
+    }
+
+    fn restore(self, s: &mut SoftState) {
+        match self.color {
+            Some((Some(id), tex)) => {
+                s.textures.insert(id, tex);
+            }
+            Some((None, tex)) => s.screen = tex,
+            None => (),
+        }
+        match self.depth {
+            Some((Some(id), tex)) => {
+                s.textures.insert(id, tex);
+            }
+            Some((None, tex)) => s.screen_depth = tex,
+            None => (),
+        }
+    }
+
+    fn size(&self) -> (u32, u32) {
+        self.color
+            .as_ref()
+            .or(self.depth.as_ref())
+            .map(|(_, t)| (t.width, t.height))
+            .unwrap_or((0, 0))
+    }
+}
+
+struct ShadedVertex {
+    // window coordinates and 1 / w
+    position: [f32; 4],
+    varyings: Vec<f32>,
+}
+
+/// Pure Rust reference backend rasterizing into in-memory textures and a screen buffer.
+///
+/// Shaders are Rust closures registered for the exact GLSL source later passed to `Program::new`,
+/// so the same program code runs on the web and here. Near plane clipping is not performed,
+/// primitives with any vertex behind the camera are dropped, and there are no mipmaps.
+/// There is no stencil buffer either, stencil test, functions, ops, masks and stencil clears
+/// are ignored, so drawing behaves as if the test always passed. Every extension is reported
+/// as supported, code paths taken without an extension are only covered by `MockBackend`.
+#[derive(Clone)]
+pub struct SoftwareBackend(Rc<RefCell<SoftState>>);
+
+impl SoftwareBackend {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self(Rc::new(RefCell::new(SoftState {
+            next_id: 0,
+            vertex_shaders: HashMap::new(),
+            fragment_shaders: HashMap::new(),
+            shaders: HashMap::new(),
+            programs: HashMap::new(),
+            uniform_names: HashMap::new(),
+            buffers: HashMap::new(),
+            textures: HashMap::new(),
+            framebuffers: HashMap::new(),
+            vertex_arrays: HashMap::new(),
+            default_vertex_array: SoftVertexArray::default(),
+            screen: SoftTexture::new(width, height, GL::UNSIGNED_BYTE),
+            screen_depth: SoftTexture::new(width, height, GL::FLOAT),
+            capabilities: BTreeSet::new(),
+            fixed: FixedFunction::new(width, height),
+            viewport: [0, 0, width as i32, height as i32],
+            clear_color: [0., 0., 0., 0.],
+            clear_depth: 1.,
+            program: None,
+            framebuffer: None,
+            vertex_array: None,
+            array_buffer: None,
+            element_buffer: None,
+            active_unit: 0,
+            units: HashMap::new(),
+            attrib_arrays: BTreeSet::new(),
+            pointers: HashMap::new(),
+            divisors: HashMap::new(),
+        })))
+    }
+
+    /// Registers closure to run in place of vertex shader with given source.
+    pub fn vertex_shader<F>(&self, source: &str, shader: F)
+    where
+        F: Fn(&SoftUniforms, &Attributes) -> ([f32; 4], Vec<f32>) + 'static,
+    {
+        self.0.borrow_mut().vertex_shaders.insert(source.to_string(), Rc::new(shader));
+    }
+
+    /// Registers closure to run in place of fragment shader with given source.
+    pub fn fragment_shader<F>(&self, source: &str, shader: F)
+    where
+        F: Fn(&SoftUniforms, &[f32]) -> Option<[f32; 4]> + 'static,
+    {
+        self.0.borrow_mut().fragment_shaders.insert(source.to_string(), Rc::new(shader));
+    }
+
+    /// Contents of the default framebuffer.
+    pub fn screen(&self) -> Image {
+        self.0.borrow().screen.image()
+    }
+
+    pub fn texture(&self, texture: TextureId) -> Option<Image> {
+        self.0.borrow().textures.get(&texture).map(|t| t.image())
+    }
+
+    fn fetch(s: &SoftState, index: u32, vertex: u32, instance: u32) -> [f32; 4] {
+        let mut result = [0., 0., 0., 1.];
+        if !s.attrib_arrays.contains(&index) {
+            return result;
+        }
+        let vertex = match s.divisors.get(&index) {
+            Some(&divisor) if divisor > 0 => instance / divisor,
+            _ => vertex,
+        };
+        let (p, data) = match s.pointers.get(&index).and_then(|p| s.buffers.get(&p.buffer).map(|d| (p, d))) {
+            Some(v) => v,
+            None => return result,
+        };
+        let component = match p.data_type {
+            GL::FLOAT => 4,
+            GL::SHORT | GL::UNSIGNED_SHORT => 2,
+            _ => 1,
+        };
+        let stride = if p.stride == 0 { p.size * component } else { p.stride };
+        let start = (p.offset + stride * vertex as i32) as usize;
+        for (i, r) in result.iter_mut().enumerate().take(p.size as usize) {
+            let at = start + i * component as usize;
+            let bytes = match data.get(at..at + component as usize) {
+                Some(b) => b,
+                None => break,
+            };
+            *r = match p.data_type {
+                GL::FLOAT => f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+                GL::SHORT => {
+                    let v = i16::from_ne_bytes([bytes[0], bytes[1]]) as f32;
+                    if p.normalized { (v / i16::MAX as f32).max(-1.) } else { v }
+                }
+                GL::UNSIGNED_SHORT => {
+                    let v = u16::from_ne_bytes([bytes[0], bytes[1]]) as f32;
+                    if p.normalized { v / u16::MAX as f32 } else { v }
+                }
+                GL::BYTE => {
+                    let v = bytes[0] as i8 as f32;
+                    if p.normalized { (v / i8::MAX as f32).max(-1.) } else { v }
+                }
+                _ => {
+                    let v = bytes[0] as f32;
+                    if p.normalized { v / u8::MAX as f32 } else { v }
+                }
+            };
+        }
+        result
+    }
+
+    fn indices(s: &SoftState, count: i32, data_type: u32, offset: i32) -> Vec<u32> {
+        let data = match s.element_buffer.and_then(|b| s.buffers.get(&b)) {
+            Some(d) => d,
+            None => return vec![],
+        };
+        let size = match data_type {
+            GL::UNSIGNED_INT => 4,
+            GL::UNSIGNED_SHORT => 2,
+            _ => 1,
+        };
+        data.get(offset as usize..)
+            .unwrap_or(&[])
+            .chunks_exact(size)
+            .take(count as usize)
+            .map(|c| match size {
+                4 => u32::from_ne_bytes([c[0], c[1], c[2], c[3]]),
+                2 => u16::from_ne_bytes([c[0], c[1]]) as u32,
+                _ => c[0] as u32,
+            })
+            .collect()
+    }
+
+    fn draw(&self, mode: u32, indices: &[u32], instances: u32) {
+        for instance in 0..instances {
+            self.draw_instance(mode, indices, instance);
+        }
+    }
+
+    fn draw_instance(&self, mode: u32, indices: &[u32], instance: u32) {
+        let mut guard = self.0.borrow_mut();
+        let s = &mut *guard;
+        let program = match s.program.and_then(|p| s.programs.get(&p)).and_then(|p| p.linked.as_ref()) {
+            Some(p) => p,
+            None => return,
+        };
+        let vertex = program.vertex.clone();
+        let fragment = program.fragment.clone();
+        let attributes = program.attributes.clone();
+        let values = program.values.clone();
+
+        let mut surface = Surface::take(s);
+        let (width, height) = surface.size();
+        let [vx, vy, vw, vh] = s.viewport;
+        let uniforms = SoftUniforms { values: &values, units: &s.units, textures: &s.textures };
+
+        let shaded = indices
+            .iter()
+            .map(|&i| {
+                let values = (0..attributes.len() as u32).map(|a| Self::fetch(s, a, i, instance)).collect::<Vec<_>>();
+                let ([x, y, z, w], varyings) = vertex(&uniforms, &Attributes { names: &attributes, values: &values });
+                ShadedVertex {
+                    position: [
+                        vx as f32 + (x / w + 1.) * 0.5 * vw as f32,
+                        vy as f32 + (y / w + 1.) * 0.5 * vh as f32,
+                        (z / w + 1.) * 0.5,
+                        1. / w,
+                    ],
+                    varyings,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let raster = Raster {
+            uniforms: &uniforms,
+            fragment: &fragment,
+            width,
+            height,
+            depth_test: s.capabilities.contains(&GL::DEPTH_TEST),
+            cull: s.capabilities.contains(&GL::CULL_FACE),
+            blend: s.capabilities.contains(&GL::BLEND),
+            scissor_test: s.capabilities.contains(&GL::SCISSOR_TEST),
+            polygon_offset: s.capabilities.contains(&GL::POLYGON_OFFSET_FILL),
+            fixed: s.fixed,
+        };
+
+        let triangles: Vec<[usize; 3]> = match mode {
+            GL::TRIANGLES => (0..shaded.len() / 3).map(|t| [3 * t, 3 * t + 1, 3 * t + 2]).collect(),
+            GL::TRIANGLE_STRIP => (2..shaded.len())
+                .map(|i| if i % 2 == 0 { [i - 2, i - 1, i] } else { [i - 1, i - 2, i] })
+                .collect(),
+            GL::TRIANGLE_FAN => (2..shaded.len()).map(|i| [0, i - 1, i]).collect(),
+            _ => vec![],
+        };
+        let lines: Vec<[usize; 2]> = match mode {
+            GL::LINES => (0..shaded.len() / 2).map(|l| [2 * l, 2 * l + 1]).collect(),
+            GL::LINE_STRIP => (1..shaded.len()).map(|i| [i - 1, i]).collect(),
+            GL::LINE_LOOP if shaded.len() > 1 => (1..=shaded.len()).map(|i| [i - 1, i % shaded.len()]).collect(),
+            _ => vec![],
+        };
+
+        for [a, b, c] in triangles {
+            raster.triangle(&mut surface, [&shaded[a], &shaded[b], &shaded[c]]);
+        }
+        for [a, b] in lines {
+            raster.line(&mut surface, &shaded[a], &shaded[b]);
+        }
+        if mode == GL::POINTS {
+            for v in shaded.iter() {
+                raster.fragment(&mut surface, v.position[0] as i32, v.position[1] as i32, v.position[2], &v.varyings);
+            }
+        }
+
+        surface.restore(s);
+    }
+}
+
+impl ShadedVertex {
+    // positive w, also rejects NaN
+    fn visible(&self) -> bool {
+        self.position[3] > 0.
+    }
+}
+
+struct Raster<'a> {
+    uniforms: &'a SoftUniforms<'a>,
+    fragment: &'a FragmentShader,
+    width: u32,
+    height: u32,
+    depth_test: bool,
+    cull: bool,
+    blend: bool,
+    scissor_test: bool,
+    polygon_offset: bool,
+    fixed: FixedFunction,
+}
+
+fn edge(a: [f32; 4], b: [f32; 4], x: f32, y: f32) -> f32 {
+    (b[0] - a[0]) * (y - a[1]) - (b[1] - a[1]) * (x - a[0])
+}
+
+impl<'a> Raster<'a> {
+    fn fragment(&self, surface: &mut Surface, x: i32, y: i32, z: f32, varyings: &[f32]) {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return;
+        }
+        let [sx, sy, sw, sh] = self.fixed.scissor;
+        if self.scissor_test && (x < sx || y < sy || x >= sx + sw || y >= sy + sh) {
+            return;
+        }
+        let idx = (y as u32 * self.width + x as u32) as usize;
+        let depth = surface.depth.as_mut().filter(|_| self.depth_test);
+        if depth.as_ref().map(|(_, d)| !compare(self.fixed.depth_func, z, d.texels[idx][0])).unwrap_or(false) {
+            return;
+        }
+        let c = match (self.fragment)(self.uniforms, varyings) {
+            Some(c) => c,
+            None => return,
+        };
+        if let Some((_, depth)) = depth.filter(|_| self.fixed.depth_mask) {
+            depth.texels[idx] = [z, z, z, 1.];
+        }
+        if let Some((_, color)) = surface.color.as_mut() {
+            let dst = color.texels[idx];
+            let src = if self.blend {
+                // fixed point targets clamp fragment color before blending
+                let src = if color.data_type == GL::FLOAT { c } else { c.map(|v| v.clamp(0., 1.)) };
+                blend(&self.fixed, src, dst)
+            } else {
+                c
+            };
+            let mut out = dst;
+            for (i, o) in out.iter_mut().enumerate() {
+                if self.fixed.color_mask[i] {
+                    *o = src[i];
+                }
+            }
+            color.write(idx, out);
+        }
+    }
+
+    fn triangle(&self, surface: &mut Surface, v: [&ShadedVertex; 3]) {
+        if !v.iter().all(|v| v.visible()) {
+            return;
+        }
+        let [a, b, c] = [v[0].position, v[1].position, v[2].position];
+        let area = edge(a, b, c[0], c[1]);
+        let front = if self.fixed.front_face == GL::CW { area < 0. } else { area > 0. };
+        let culled = match self.fixed.cull_face {
+            GL::FRONT => front,
+            GL::FRONT_AND_BACK => true,
+            _ => !front,
+        };
+        if area == 0. || (self.cull && culled) {
+            return;
+        }
+        let offset = if self.polygon_offset {
+            let dzdx = ((b[2] - a[2]) * (c[1] - a[1]) - (c[2] - a[2]) * (b[1] - a[1])) / area;
+            let dzdy = ((c[2] - a[2]) * (b[0] - a[0]) - (b[2] - a[2]) * (c[0] - a[0])) / area;
+            let [factor, units] = self.fixed.polygon_offset;
+            factor * dzdx.abs().max(dzdy.abs()) + units / (1 << 24) as f32
+        } else {
+            0.
+        };
+
+        let min_x = a[0].min(b[0]).min(c[0]).floor().max(0.) as i32;
+        let max_x = a[0].max(b[0]).max(c[0]).ceil().min(self.width as f32) as i32;
+        let min_y = a[1].min(b[1]).min(c[1]).floor().max(0.) as i32;
+        let max_y = a[1].max(b[1]).max(c[1]).ceil().min(self.height as f32) as i32;
+
+        let mut varyings = vec![0.; v[0].varyings.len()];
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                let l = [edge(b, c, px, py) / area, edge(c, a, px, py) / area, edge(a, b, px, py) / area];
+                if l.iter().any(|l| *l < 0.) {
+                    continue;
+                }
+                let z = l[0] * a[2] + l[1] * b[2] + l[2] * c[2] + offset;
+                let w = [l[0] * a[3], l[1] * b[3], l[2] * c[3]];
+                let denom = w[0] + w[1] + w[2];
+                for (i, out) in varyings.iter_mut().enumerate() {
+                    *out = (0..3)
+                        .map(|k| w[k] * v[k].varyings.get(i).copied().unwrap_or(0.))
+                        .sum::<f32>()
+                        / denom;
+                }
+                self.fragment(surface, x, y, z, &varyings);
+            }
+        }
+    }
+
+    fn line(&self, surface: &mut Surface, a: &ShadedVertex, b: &ShadedVertex) {
+        if !(a.visible() && b.visible()) {
+            return;
+        }
+        let (dx, dy) = (b.position[0] - a.position[0], b.position[1] - a.position[1]);
+        let steps = dx.abs().max(dy.abs()).ceil().max(1.) as i32;
+        let mut varyings = vec![0.; a.varyings.len()];
+        for step in 0..=steps {
+            let t = step as f32 / steps as f32;
+            for (i, out) in varyings.iter_mut().enumerate() {
+                let (va, vb) = (a.varyings[i], b.varyings.get(i).copied().unwrap_or(0.));
+                *out = va + (vb - va) * t;
+            }
+            self.fragment(
+                surface,
+                (a.position[0] + dx * t) as i32,
+                (a.position[1] + dy * t) as i32,
+                a.position[2] + (b.position[2] - a.position[2]) * t,
+                &varyings,
+            );
+        }
+    }
+}
+
+impl GlBackend for SoftwareBackend {
+    fn get_extension(&self, _name: &str) -> bool {
+        true
+    }
+
+    fn is_context_lost(&self) -> bool {
+        false
+    }
+
+    fn enable(&self, cap: u32) {
+        self.0.borrow_mut().capabilities.insert(cap);
+    }
+
+    fn disable(&self, cap: u32) {
+        self.0.borrow_mut().capabilities.remove(&cap);
+    }
+
+    fn viewport(&self, x: i32, y: i32, width: i32, height: i32) {
+        self.0.borrow_mut().viewport = [x, y, width, height];
+    }
+
+    fn clear_color(&self, r: f32, g: f32, b: f32, a: f32) {
+        self.0.borrow_mut().clear_color = [r, g, b, a];
+    }
+
+    fn clear_depth(&self, depth: f32) {
+        self.0.borrow_mut().clear_depth = depth;
+    }
+
+    fn clear_stencil(&self, _stencil: i32) {}
+
+    fn clear(&self, mask: u32) {
+        let mut s = self.0.borrow_mut();
+        let mut surface = Surface::take(&mut s);
+        if mask & GL::COLOR_BUFFER_BIT != 0 {
+            if let Some((_, color)) = surface.color.as_mut() {
+                let c = s.clear_color;
+                (0..color.texels.len()).for_each(|i| color.write(i, c));
+            }
+        }
+        if mask & GL::DEPTH_BUFFER_BIT != 0 {
+            if let Some((_, depth)) = surface.depth.as_mut() {
+                let d = s.clear_depth.clamp(0., 1.);
+                depth.texels.iter_mut().for_each(|t| *t = [d, d, d, 1.]);
+            }
+        }
+        surface.restore(&mut s);
+    }
+
+    fn blend_equation_separate(&self, mode_rgb: u32, mode_alpha: u32) {
+        self.0.borrow_mut().fixed.blend_equation = [mode_rgb, mode_alpha];
+    }
+
+    fn blend_func_separate(&self, src_rgb: u32, dst_rgb: u32, src_alpha: u32, dst_alpha: u32) {
+        self.0.borrow_mut().fixed.blend_func = [src_rgb, dst_rgb, src_alpha, dst_alpha];
+    }
+
+    fn blend_color(&self, r: f32, g: f32, b: f32, a: f32) {
+        self.0.borrow_mut().fixed.blend_color = [r, g, b, a];
+    }
+
+    fn depth_func(&self, func: u32) {
+        self.0.borrow_mut().fixed.depth_func = func;
+    }
+
+    fn depth_mask(&self, flag: bool) {
+        self.0.borrow_mut().fixed.depth_mask = flag;
+    }
+
+    fn cull_face(&self, mode: u32) {
+        self.0.borrow_mut().fixed.cull_face = mode;
+    }
+
+    fn front_face(&self, mode: u32) {
+        self.0.borrow_mut().fixed.front_face = mode;
+    }
+
+    fn color_mask(&self, r: bool, g: bool, b: bool, a: bool) {
+        self.0.borrow_mut().fixed.color_mask = [r, g, b, a];
+    }
+
+    fn polygon_offset(&self, factor: f32, units: f32) {
+        self.0.borrow_mut().fixed.polygon_offset = [factor, units];
+    }
+
+    // there is no stencil buffer, stencil state is accepted and ignored
+    fn stencil_func_separate(&self, _face: u32, _func: u32, _reference: i32, _mask: u32) {}
+
+    fn stencil_op_separate(&self, _face: u32, _fail: u32, _depth_fail: u32, _pass: u32) {}
+
+    fn stencil_mask_separate(&self, _face: u32, _mask: u32) {}
+
+    fn scissor(&self, x: i32, y: i32, width: i32, height: i32) {
+        self.0.borrow_mut().fixed.scissor = [x, y, width, height];
+    }
+
+    fn create_shader(&self, shader_type: u32) -> Option<ShaderId> {
+        let mut s = self.0.borrow_mut();
+        let id = ShaderId(s.id());
+        s.shaders.insert(id, SoftShader { shader_type, source: String::new(), compiled: false, log: None });
+        Some(id)
+    }
+
+    fn shader_source(&self, shader: ShaderId, source: &str) {
+        if let Some(sh) = self.0.borrow_mut().shaders.get_mut(&shader) {
+            sh.source = source.to_string();
+        }
+    }
+
+    fn compile_shader(&self, shader: ShaderId) {
+        let mut guard = self.0.borrow_mut();
+        let s = &mut *guard;
+        if let Some(sh) = s.shaders.get_mut(&shader) {
+            sh.compiled = match sh.shader_type {
+                GL::VERTEX_SHADER => s.vertex_shaders.contains_key(&sh.source),
+                _ => s.fragment_shaders.contains_key(&sh.source),
+            };
+            sh.log = Some(if sh.compiled { String::new() } else { "no closure registered for shader source".to_string() });
+        }
+    }
+
+    fn get_shader_parameter(&self, shader: ShaderId, pname: u32) -> i32 {
+        match (self.0.borrow().shaders.get(&shader), pname) {
+            (Some(sh), GL::COMPILE_STATUS) => sh.compiled as i32,
+            (Some(sh), GL::SHADER_TYPE) => sh.shader_type as i32,
+            _ => 0,
+        }
+    }
+
+    fn get_shader_info_log(&self, shader: ShaderId) -> Option<String> {
+        self.0.borrow().shaders.get(&shader).map(|sh| sh.log.clone().unwrap_or_default())
+    }
+
+    fn delete_shader(&self, shader: ShaderId) {
+        self.0.borrow_mut().shaders.remove(&shader);
+    }
+
+    fn create_program(&self) -> Option<ProgramId> {
+        let mut s = self.0.borrow_mut();
+        let id = ProgramId(s.id());
+        s.programs.insert(id, SoftProgram::default());
+        Some(id)
+    }
+
+    fn attach_shader(&self, program: ProgramId, shader: ShaderId) {
+        if let Some(p) = self.0.borrow_mut().programs.get_mut(&program) {
+            p.shaders.push(shader);
+        }
+    }
+
+    fn detach_shader(&self, program: ProgramId, shader: ShaderId) {
+        if let Some(p) = self.0.borrow_mut().programs.get_mut(&program) {
+            p.shaders.retain(|sh| *sh != shader);
+        }
+    }
+
+    fn link_program(&self, program: ProgramId) {
+        let mut guard = self.0.borrow_mut();
+        let s = &mut *guard;
+        let shaders = match s.programs.get(&program) {
+            Some(p) => p.shaders.iter().filter_map(|id| s.shaders.get(id)).collect::<Vec<_>>(),
+            None => return,
+        };
+        let find = |ty: u32| shaders.iter().find(|sh| sh.shader_type == ty && sh.compiled);
+        let (vs, fs) = match (find(GL::VERTEX_SHADER), find(GL::FRAGMENT_SHADER)) {
+            (Some(vs), Some(fs)) => (vs, fs),
+            _ => {
+                if let Some(p) = s.programs.get_mut(&program) {
+                    p.linked = None;
+                    p.log = "program needs compiled vertex and fragment shaders".to_string();
+                }
+                return;
+            }
+        };
+
+        let mut attributes = vec![];
+        let mut uniforms = vec![];
+        let mut active_attributes = vec![];
+        let mut active_uniforms = vec![];
+        for decl in declarations(&vs.source).into_iter().chain(declarations(&fs.source)) {
+            if decl.qualifier == "attribute" {
+                active_attributes.push(decl.active_info());
+                // one entry per location, matrix columns follow each other
+                for _ in 0..decl.locations() {
+                    attributes.push(decl.name.clone());
+                }
+            } else if !uniforms.iter().any(|u: &Declaration| u.name == decl.name) {
+                active_uniforms.push(decl.active_info());
+                uniforms.push(decl);
+            }
+        }
+        let vertex = s.vertex_shaders[&vs.source].clone();
+        let fragment = s.fragment_shaders[&fs.source].clone();
+
+        let mut locations = HashMap::new();
+        for decl in uniforms {
+            let elements = (0..decl.size).map(|_| UniformLocation(s.id())).collect::<Vec<_>>();
+            for (index, loc) in elements.iter().enumerate() {
+                s.uniform_names.insert(*loc, SoftUniform {
+                    program,
+                    name: decl.name.clone(),
+                    gl_type: decl.gl_type(),
+                    index: index as i32,
+                    size: decl.size,
+                });
+            }
+            for (name, i) in decl.location_names() {
+                locations.insert(name, elements[i as usize]);
+            }
+        }
+        if let Some(p) = s.programs.get_mut(&program) {
+            p.linked = Some(Linked {
+                vertex,
+                fragment,
+                attributes,
+                locations,
+                values: HashMap::new(),
+                active_attributes,
+                active_uniforms,
+            });
+            p.log = String::new();
+        }
+    }
+
+    fn validate_program(&self, _program: ProgramId) {}
+
+    fn get_program_parameter(&self, program: ProgramId, pname: u32) -> i32 {
+        match (self.0.borrow().programs.get(&program), pname) {
+            (Some(p), GL::LINK_STATUS) | (Some(p), GL::VALIDATE_STATUS) => p.linked.is_some() as i32,
+            (Some(p), GL::ATTACHED_SHADERS) => p.shaders.len() as i32,
+            (Some(p), GL::ACTIVE_ATTRIBUTES) => p.linked.as_ref().map_or(0, |l| l.active_attributes.len() as i32),
+            (Some(p), GL::ACTIVE_UNIFORMS) => p.linked.as_ref().map_or(0, |l| l.active_uniforms.len() as i32),
+            _ => 0,
+        }
+    }
+
+    fn get_program_info_log(&self, program: ProgramId) -> Option<String> {
+        self.0.borrow().programs.get(&program).map(|p| p.log.clone())
+    }
+
+    fn use_program(&self, program: Option<ProgramId>) {
+        self.0.borrow_mut().program = program;
+    }
+
+    fn delete_program(&self, program: ProgramId) {
+        let mut s = self.0.borrow_mut();
+        s.programs.remove(&program);
+        s.uniform_names.retain(|_, u| u.program != program);
+        if s.program == Some(program) {
+            s.program = None;
+        }
+    }
+
+    fn get_attrib_location(&self, program: ProgramId, name: &str) -> i32 {
+        self.0
+            .borrow()
+            .programs
+            .get(&program)
+            .and_then(|p| p.linked.as_ref())
+            .and_then(|l| l.attributes.iter().position(|a| a == name))
+            .map(|i| i as i32)
+            .unwrap_or(-1)
+    }
+
+    fn get_uniform_location(&self, program: ProgramId, name: &str) -> Option<UniformLocation> {
+        self.0
+            .borrow()
+            .programs
+            .get(&program)
+            .and_then(|p| p.linked.as_ref())
+            .and_then(|l| l.locations.get(name).copied())
+    }
+
+    fn get_active_attrib(&self, program: ProgramId, index: u32) -> Option<ActiveInfo> {
+        let s = self.0.borrow();
+        s.programs.get(&program)?.linked.as_ref()?.active_attributes.get(index as usize).cloned()
+    }
+
+    fn get_active_uniform(&self, program: ProgramId, index: u32) -> Option<ActiveInfo> {
+        let s = self.0.borrow();
+        s.programs.get(&program)?.linked.as_ref()?.active_uniforms.get(index as usize).cloned()
+    }
+
+    fn uniform1i(&self, location: UniformLocation, v: i32) {
+        self.set_uniform(location, UniformValue::Int(v))
+    }
+
+    fn uniform1f(&self, location: UniformLocation, v: f32) {
+        self.set_uniform(location, UniformValue::Float(v))
+    }
+
+    fn uniform1iv(&self, location: UniformLocation, v: &[i32]) {
+        self.set_uniform(location, UniformValue::IntArray(v.to_vec()))
+    }
+
+    fn uniform1fv(&self, location: UniformLocation, v: &[f32]) {
+        self.set_uniform(location, UniformValue::FloatArray(v.to_vec()))
+    }
+
+    fn uniform2fv(&self, location: UniformLocation, v: &[f32]) {
+        self.set_uniform(location, UniformValue::FloatVec2(v.to_vec()))
+    }
+
+    fn uniform3fv(&self, location: UniformLocation, v: &[f32]) {
+        self.set_uniform(location, UniformValue::FloatVec3(v.to_vec()))
+    }
+
+    fn uniform4fv(&self, location: UniformLocation, v: &[f32]) {
+        self.set_uniform(location, UniformValue::FloatVec4(v.to_vec()))
+    }
+
+    fn uniform2iv(&self, location: UniformLocation, v: &[i32]) {
+        self.set_uniform(location, UniformValue::IntVec2(v.to_vec()))
+    }
+
+    fn uniform3iv(&self, location: UniformLocation, v: &[i32]) {
+        self.set_uniform(location, UniformValue::IntVec3(v.to_vec()))
+    }
+
+    fn uniform4iv(&self, location: UniformLocation, v: &[i32]) {
+        self.set_uniform(location, UniformValue::IntVec4(v.to_vec()))
+    }
+
+    fn uniform_matrix2fv(&self, location: UniformLocation, transpose: bool, v: &[f32]) {
+        self.set_uniform(location, UniformValue::FloatMat2 { transpose, data: v.to_vec() })
+    }
+
+    fn uniform_matrix3fv(&self, location: UniformLocation, transpose: bool, v: &[f32]) {
+        self.set_uniform(location, UniformValue::FloatMat3 { transpose, data: v.to_vec() })
+    }
+
+    fn uniform_matrix4fv(&self, location: UniformLocation, transpose: bool, v: &[f32]) {
+        self.set_uniform(location, UniformValue::FloatMat4 { transpose, data: v.to_vec() })
+    }
+
+    fn create_buffer(&self) -> Option<BufferId> {
+        let mut s = self.0.borrow_mut();
+        let id = BufferId(s.id());
+        s.buffers.insert(id, vec![]);
+        Some(id)
+    }
+
+    fn bind_buffer(&self, target: u32, buffer: Option<BufferId>) {
+        let mut s = self.0.borrow_mut();
+        match target {
+            GL::ARRAY_BUFFER => s.array_buffer = buffer,
+            GL::ELEMENT_ARRAY_BUFFER => s.element_buffer = buffer,
+            _ => (),
+        }
+    }
+
+    fn buffer_data(&self, target: u32, data: &[u8], _usage: u32) {
+        let mut s = self.0.borrow_mut();
+        let bound = match target {
+            GL::ARRAY_BUFFER => s.array_buffer,
+            GL::ELEMENT_ARRAY_BUFFER => s.element_buffer,
+            _ => None,
+        };
+        if let Some(buf) = bound.and_then(|b| s.buffers.get_mut(&b)) {
+            *buf = data.to_vec();
+        }
+    }
+
+    /// Writes past the end of the buffer are ignored, GL reports them as `INVALID_VALUE`.
+    fn buffer_sub_data(&self, target: u32, offset: i32, data: &[u8]) {
+        let mut s = self.0.borrow_mut();
+        let bound = match target {
+            GL::ARRAY_BUFFER => s.array_buffer,
+            GL::ELEMENT_ARRAY_BUFFER => s.element_buffer,
+            _ => None,
+        };
+        let range = offset as usize..offset as usize + data.len();
+        if let Some(buf) = bound.and_then(|b| s.buffers.get_mut(&b)).filter(|b| offset >= 0 && range.end <= b.len()) {
+            buf[range].copy_from_slice(data);
+        }
+    }
+
+    fn delete_buffer(&self, buffer: BufferId) {
+        let mut s = self.0.borrow_mut();
+        s.buffers.remove(&buffer);
+        if s.array_buffer == Some(buffer) {
+            s.array_buffer = None;
+        }
+        if s.element_buffer == Some(buffer) {
+            s.element_buffer = None;
+        }
+    }
+
+    fn enable_vertex_attrib_array(&self, index: u32) {
+        self.0.borrow_mut().attrib_arrays.insert(index);
+    }
+
+    fn disable_vertex_attrib_array(&self, index: u32) {
+        self.0.borrow_mut().attrib_arrays.remove(&index);
+    }
+
+    fn vertex_attrib_pointer(&self, index: u32, size: i32, data_type: u32, normalized: bool, stride: i32, offset: i32) {
+        let mut s = self.0.borrow_mut();
+        if let Some(buffer) = s.array_buffer {
+            s.pointers.insert(index, Pointer { buffer, size, data_type, normalized, stride, offset });
+        }
+    }
+
+    fn draw_elements(&self, mode: u32, count: i32, data_type: u32, offset: i32) {
+        self.draw_elements_instanced(mode, count, data_type, offset, 1);
+    }
+
+    fn draw_arrays(&self, mode: u32, first: i32, count: i32) {
+        self.draw_arrays_instanced(mode, first, count, 1);
+    }
+
+    fn vertex_attrib_divisor(&self, index: u32, divisor: u32) {
+        self.0.borrow_mut().divisors.insert(index, divisor);
+    }
+
+    fn draw_elements_instanced(&self, mode: u32, count: i32, data_type: u32, offset: i32, instances: i32) {
+        let indices = Self::indices(&self.0.borrow(), count, data_type, offset);
+        self.draw(mode, &indices, instances.max(0) as u32);
+    }
+
+    fn draw_arrays_instanced(&self, mode: u32, first: i32, count: i32, instances: i32) {
+        let indices = (first.max(0) as u32..(first + count).max(0) as u32).collect::<Vec<_>>();
+        self.draw(mode, &indices, instances.max(0) as u32);
+    }
+
+    fn create_texture(&self) -> Option<TextureId> {
+        let mut s = self.0.borrow_mut();
+        let id = TextureId(s.id());
+        s.textures.insert(id, SoftTexture::new(0, 0, GL::UNSIGNED_BYTE));
+        Some(id)
+    }
+
+    fn active_texture(&self, unit: u32) {
+        self.0.borrow_mut().active_unit = unit - GL::TEXTURE0;
+    }
+
+    fn bind_texture(&self, _target: u32, texture: Option<TextureId>) {
+        let mut s = self.0.borrow_mut();
+        let unit = s.active_unit;
+        match texture {
+            Some(t) => s.units.insert(unit, t),
+            None => s.units.remove(&unit),
+        };
+    }
+
+    fn tex_image_2d(
+        &self,
+        _target: u32,
+        level: i32,
+        _internal_format: i32,
+        width: i32,
+        height: i32,
+        format: u32,
+        data_type: u32,
+        data: Option<&[u8]>,
+    ) -> Result<(), String> {
+        if level != 0 {
+            return Ok(());
+        }
+        let mut s = self.0.borrow_mut();
+        let tex = s.bound_texture().ok_or("No texture bound")?;
+        let (width, height) = (width as u32, height as u32);
+        let mut texels = data.map(|d| decode_texels(format, data_type, d)).unwrap_or_default();
+        texels.resize((width * height) as usize, [0., 0., 0., 0.]);
+        tex.width = width;
+        tex.height = height;
+        tex.data_type = if format == GL::DEPTH_COMPONENT { GL::FLOAT } else { data_type };
+        tex.texels = texels;
+
+        Ok(())
+    }
+
+    fn tex_parameteri(&self, _target: u32, pname: u32, param: i32) {
+        let mut s = self.0.borrow_mut();
+        if let Some(tex) = s.bound_texture() {
+            match pname {
+                GL::TEXTURE_MIN_FILTER => tex.min_filter = param as u32,
+                GL::TEXTURE_MAG_FILTER => tex.mag_filter = param as u32,
+                GL::TEXTURE_WRAP_S => tex.wrap_s = param as u32,
+                GL::TEXTURE_WRAP_T => tex.wrap_t = param as u32,
+                _ => (),
+            }
+        }
+    }
+
+    fn delete_texture(&self, texture: TextureId) {
+        let mut s = self.0.borrow_mut();
+        s.textures.remove(&texture);
+        s.units.retain(|_, t| *t != texture);
+    }
+
+    fn create_framebuffer(&self) -> Option<FramebufferId> {
+        let mut s = self.0.borrow_mut();
+        let id = FramebufferId(s.id());
+        s.framebuffers.insert(id, Attachments::default());
+        Some(id)
+    }
+
+    fn bind_framebuffer(&self, _target: u32, framebuffer: Option<FramebufferId>) {
+        self.0.borrow_mut().framebuffer = framebuffer;
+    }
+
+    fn framebuffer_texture_2d(&self, _target: u32, attachment: u32, _tex_target: u32, texture: Option<TextureId>, _level: i32) {
+        let mut s = self.0.borrow_mut();
+        let bound = s.framebuffer;
+        if let Some(a) = bound.and_then(|fb| s.framebuffers.get_mut(&fb)) {
+            match attachment {
+                GL::DEPTH_ATTACHMENT => a.depth = texture,
+                _ => a.color = texture,
+            }
+        }
+    }
+
+    fn check_framebuffer_status(&self, _target: u32) -> u32 {
+        let s = self.0.borrow();
+        match s.framebuffer.and_then(|fb| s.framebuffers.get(&fb)) {
+            Some(a) if a.color.is_none() && a.depth.is_none() => GL::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT,
+            _ => GL::FRAMEBUFFER_COMPLETE,
+        }
+    }
+
+    fn delete_framebuffer(&self, framebuffer: FramebufferId) {
+        let mut s = self.0.borrow_mut();
+        s.framebuffers.remove(&framebuffer);
+        if s.framebuffer == Some(framebuffer) {
+            s.framebuffer = None;
+        }
+    }
+
+    fn create_vertex_array(&self) -> Option<VertexArrayId> {
+        let mut s = self.0.borrow_mut();
+        let id = VertexArrayId(s.id());
+        s.vertex_arrays.insert(id, SoftVertexArray::default());
+        Some(id)
+    }
+
+    fn bind_vertex_array(&self, vertex_array: Option<VertexArrayId>) {
+        self.0.borrow_mut().switch_vertex_array(vertex_array);
+    }
+
+    fn delete_vertex_array(&self, vertex_array: VertexArrayId) {
+        let mut s = self.0.borrow_mut();
+        if s.vertex_array == Some(vertex_array) {
+            s.switch_vertex_array(None);
+        }
+        s.vertex_arrays.remove(&vertex_array);
+    }
+}
+
+impl SoftwareBackend {
+    fn set_uniform(&self, location: UniformLocation, value: UniformValue) {
+        let mut guard = self.0.borrow_mut();
+        let s = &mut *guard;
+        let Some(u) = s.uniform_names.get(&location) else { return };
+        let Some(linked) = s.programs.get_mut(&u.program).and_then(|p| p.linked.as_mut()) else { return };
+        if u.size == 1 {
+            linked.values.insert(u.name.clone(), value);
+            return;
+        }
+
+        // arrays are kept element by element, `name` is an alias of `name[0]` as in GL
+        let elements = split(value, components(u.gl_type));
+        for (i, element) in (u.index..u.size).zip(elements) {
+            if i == 0 {
+                linked.values.insert(u.name.clone(), element.clone());
+            }
+            linked.values.insert(format!("{}[{}]", u.name, i), element);
+        }
+    }
+}
+
+fn components(gl_type: u32) -> usize {
+    match gl_type {
+        GL::FLOAT_VEC2 | GL::INT_VEC2 | GL::BOOL_VEC2 => 2,
+        GL::FLOAT_VEC3 | GL::INT_VEC3 | GL::BOOL_VEC3 => 3,
+        GL::FLOAT_VEC4 | GL::INT_VEC4 | GL::BOOL_VEC4 | GL::FLOAT_MAT2 => 4,
+        GL::FLOAT_MAT3 => 9,
+        GL::FLOAT_MAT4 => 16,
+        _ => 1,
+    }
+}
+
+/// Splits value uploaded to an array into per element values.
+fn split(value: UniformValue, n: usize) -> Vec<UniformValue> {
+    match value {
+        UniformValue::IntArray(v) => v.into_iter().map(UniformValue::Int).collect(),
+        UniformValue::FloatArray(v) => v.into_iter().map(UniformValue::Float).collect(),
+        UniformValue::FloatVec2(v) => v.chunks(n).map(|c| UniformValue::FloatVec2(c.to_vec())).collect(),
+        UniformValue::FloatVec3(v) => v.chunks(n).map(|c| UniformValue::FloatVec3(c.to_vec())).collect(),
+        UniformValue::FloatVec4(v) => v.chunks(n).map(|c| UniformValue::FloatVec4(c.to_vec())).collect(),
+        UniformValue::IntVec2(v) => v.chunks(n).map(|c| UniformValue::IntVec2(c.to_vec())).collect(),
+        UniformValue::IntVec3(v) => v.chunks(n).map(|c| UniformValue::IntVec3(c.to_vec())).collect(),
+        UniformValue::IntVec4(v) => v.chunks(n).map(|c| UniformValue::IntVec4(c.to_vec())).collect(),
+        UniformValue::FloatMat2 { transpose, data } =>
+            data.chunks(n).map(|c| UniformValue::FloatMat2 { transpose, data: c.to_vec() }).collect(),
+        UniformValue::FloatMat3 { transpose, data } =>
+            data.chunks(n).map(|c| UniformValue::FloatMat3 { transpose, data: c.to_vec() }).collect(),
+        UniformValue::FloatMat4 { transpose, data } =>
+            data.chunks(n).map(|c| UniformValue::FloatMat4 { transpose, data: c.to_vec() }).collect(),
+        v => vec![v],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Ctx, Pipeline, Program, UniformData};
+    use crate::attributes::{AttributeVector2, AttributeVector3};
+    use crate::mesh::Mesh;
+    use crate::texture::{EmptyFramebuffer, Viewport};
+
+    const VERTEX: &str = "attribute vec2 position; attribute vec3 color; varying vec3 v_color; void main() {}";
+    const FRAGMENT: &str = "uniform float u_brightness; varying vec3 v_color; void main() {}";
+
+    /// Triangle with a red, a green and a blue corner drawn through the whole `Ctx` / `Pipeline` stack.
+    fn triangle(size: u32) -> Image {
+        let soft = SoftwareBackend::new(size, size);
+        soft.vertex_shader(VERTEX, |_, a| {
+            let [x, y, ..] = a.get("position");
+            let [r, g, b, _] = a.get("color");
+            ([x, y, 0., 1.], vec![r, g, b])
+        });
+        soft.fragment_shader(FRAGMENT, |u, v| {
+            let k = u.float("u_brightness");
+            Some([v[0] * k, v[1] * k, v[2] * k, 1.])
+        });
+
+        let ctx = Ctx::with_backend(soft.clone()).unwrap();
+        let program = Program::new(&ctx, VERTEX, FRAGMENT).unwrap();
+        let mut mesh = Mesh::new(&ctx, &[0, 1, 2]).unwrap()
+            .with_attribute::<AttributeVector2>("position", &[[-0.8, -0.8], [0.8, -0.8], [0., 0.8]]).unwrap()
+            .with_attribute::<AttributeVector3>("color", &[[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]]).unwrap();
+        let mut output = EmptyFramebuffer::new(&ctx, Viewport::new(size, size));
+
+        Pipeline::new(&ctx)
+            .with_clear_color(Some([0.1, 0.1, 0.1, 1.]))
+            .shade(&program, vec![("u_brightness", UniformData::Scalar(0.9))], vec![&mut mesh], &mut output)
+            .unwrap();
+
+        soft.screen()
+    }
+
+    #[test]
+    fn triangle_covers_center_and_leaves_corners_clear() {
+        let image = triangle(32);
+        let pixel = |x: u32, y: u32| {
+            let i = ((y * image.width + x) * 4) as usize;
+            [image.data[i], image.data[i + 1], image.data[i + 2], image.data[i + 3]]
+        };
+
+        assert_eq!(pixel(0, 0), [26, 26, 26, 255]);
+        assert_eq!(pixel(31, 0), [26, 26, 26, 255]);
+        let [r, g, b, a] = pixel(16, 16);
+        assert!(r > 26 && g > 26 && b > 26 && a == 255);
+    }
+
+    /// Set `GLSMRS_BLESS=1` to rewrite reference images after an intended change in rendering.
+    #[cfg(feature = "png")]
+    #[test]
+    fn triangle_matches_golden_image() {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/triangle.png");
+        let image = triangle(64);
+
+        if std::env::var_os("GLSMRS_BLESS").is_some() {
+            image.save_png(&path).unwrap();
+        }
+        let golden = Image::load_png(&path).unwrap();
+
+        // leaves room for float rounding differences between platforms
+        assert!(matches!(image.max_difference(&golden), Some(d) if d <= 1));
+    }
+}
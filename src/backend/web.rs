@@ -0,0 +1,483 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{
+    AngleInstancedArrays, OesVertexArrayObject, WebGlBuffer, WebGlFramebuffer, WebGlProgram, WebGlRenderingContext,
+    WebGlShader, WebGlTexture, WebGlUniformLocation, WebGlVertexArrayObject,
+};
+
+use crate::GL;
+use super::*;
+
+/// Backend forwarding every call to a browser `WebGlRenderingContext`.
+pub struct WebBackend {
+    ctx: WebGlRenderingContext,
+    buffers: Objects<WebGlBuffer>,
+    shaders: Objects<WebGlShader>,
+    programs: Objects<WebGlProgram>,
+    textures: Objects<WebGlTexture>,
+    framebuffers: Objects<WebGlFramebuffer>,
+    vertex_arrays: Objects<WebGlVertexArrayObject>,
+    locations: Objects<WebGlUniformLocation>,
+    location_ids: RefCell<HashMap<(u32, String), u32>>,
+    instanced_arrays: RefCell<Option<AngleInstancedArrays>>,
+    vertex_array_object: RefCell<Option<OesVertexArrayObject>>,
+}
+
+fn param_to_i32(v: JsValue) -> i32 {
+    v.as_bool()
+        .map(|b| b as i32)
+        .or_else(|| v.as_f64().map(|f| f as i32))
+        .unwrap_or(0)
+}
+
+impl WebBackend {
+    pub fn new(ctx: WebGlRenderingContext) -> Self {
+        Self {
+            ctx,
+            buffers: Objects::new(),
+            shaders: Objects::new(),
+            programs: Objects::new(),
+            textures: Objects::new(),
+            framebuffers: Objects::new(),
+            vertex_arrays: Objects::new(),
+            locations: Objects::new(),
+            location_ids: RefCell::new(HashMap::new()),
+            instanced_arrays: RefCell::new(None),
+            vertex_array_object: RefCell::new(None),
+        }
+    }
+
+    pub fn context(&self) -> &WebGlRenderingContext {
+        &self.ctx
+    }
+}
+
+impl GlBackend for WebBackend {
+    fn get_extension(&self, name: &str) -> bool {
+        match self.ctx.get_extension(name) {
+            Ok(Some(ext)) => {
+                match name {
+                    "ANGLE_instanced_arrays" => *self.instanced_arrays.borrow_mut() = Some(ext.unchecked_into()),
+                    "OES_vertex_array_object" => *self.vertex_array_object.borrow_mut() = Some(ext.unchecked_into()),
+                    _ => (),
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn is_context_lost(&self) -> bool {
+        self.ctx.is_context_lost()
+    }
+
+    fn enable(&self, cap: u32) {
+        self.ctx.enable(cap)
+    }
+
+    fn disable(&self, cap: u32) {
+        self.ctx.disable(cap)
+    }
+
+    fn viewport(&self, x: i32, y: i32, width: i32, height: i32) {
+        self.ctx.viewport(x, y, width, height)
+    }
+
+    fn clear_color(&self, r: f32, g: f32, b: f32, a: f32) {
+        self.ctx.clear_color(r, g, b, a)
+    }
+
+    fn clear_depth(&self, depth: f32) {
+        self.ctx.clear_depth(depth)
+    }
+
+    fn clear_stencil(&self, stencil: i32) {
+        self.ctx.clear_stencil(stencil)
+    }
+
+    fn clear(&self, mask: u32) {
+        self.ctx.clear(mask)
+    }
+
+    fn blend_equation_separate(&self, mode_rgb: u32, mode_alpha: u32) {
+        self.ctx.blend_equation_separate(mode_rgb, mode_alpha)
+    }
+
+    fn blend_func_separate(&self, src_rgb: u32, dst_rgb: u32, src_alpha: u32, dst_alpha: u32) {
+        self.ctx.blend_func_separate(src_rgb, dst_rgb, src_alpha, dst_alpha)
+    }
+
+    fn blend_color(&self, r: f32, g: f32, b: f32, a: f32) {
+        self.ctx.blend_color(r, g, b, a)
+    }
+
+    fn depth_func(&self, func: u32) {
+        self.ctx.depth_func(func)
+    }
+
+    fn depth_mask(&self, flag: bool) {
+        self.ctx.depth_mask(flag)
+    }
+
+    fn cull_face(&self, mode: u32) {
+        self.ctx.cull_face(mode)
+    }
+
+    fn front_face(&self, mode: u32) {
+        self.ctx.front_face(mode)
+    }
+
+    fn color_mask(&self, r: bool, g: bool, b: bool, a: bool) {
+        self.ctx.color_mask(r, g, b, a)
+    }
+
+    fn polygon_offset(&self, factor: f32, units: f32) {
+        self.ctx.polygon_offset(factor, units)
+    }
+
+    fn stencil_func_separate(&self, face: u32, func: u32, reference: i32, mask: u32) {
+        self.ctx.stencil_func_separate(face, func, reference, mask)
+    }
+
+    fn stencil_op_separate(&self, face: u32, fail: u32, depth_fail: u32, pass: u32) {
+        self.ctx.stencil_op_separate(face, fail, depth_fail, pass)
+    }
+
+    fn stencil_mask_separate(&self, face: u32, mask: u32) {
+        self.ctx.stencil_mask_separate(face, mask)
+    }
+
+    fn scissor(&self, x: i32, y: i32, width: i32, height: i32) {
+        self.ctx.scissor(x, y, width, height)
+    }
+
+    fn create_shader(&self, shader_type: u32) -> Option<ShaderId> {
+        self.ctx.create_shader(shader_type).map(|s| ShaderId(self.shaders.insert(s)))
+    }
+
+    fn shader_source(&self, shader: ShaderId, source: &str) {
+        if let Some(s) = self.shaders.get(shader.0) {
+            self.ctx.shader_source(&s, source)
+        }
+    }
+
+    fn compile_shader(&self, shader: ShaderId) {
+        if let Some(s) = self.shaders.get(shader.0) {
+            self.ctx.compile_shader(&s)
+        }
+    }
+
+    fn get_shader_parameter(&self, shader: ShaderId, pname: u32) -> i32 {
+        self.shaders
+            .get(shader.0)
+            .map(|s| param_to_i32(self.ctx.get_shader_parameter(&s, pname)))
+            .unwrap_or(0)
+    }
+
+    fn get_shader_info_log(&self, shader: ShaderId) -> Option<String> {
+        self.shaders.get(shader.0).and_then(|s| self.ctx.get_shader_info_log(&s))
+    }
+
+    fn delete_shader(&self, shader: ShaderId) {
+        self.ctx.delete_shader(self.shaders.remove(shader.0).as_ref())
+    }
+
+    fn create_program(&self) -> Option<ProgramId> {
+        self.ctx.create_program().map(|p| ProgramId(self.programs.insert(p)))
+    }
+
+    fn attach_shader(&self, program: ProgramId, shader: ShaderId) {
+        if let (Some(p), Some(s)) = (self.programs.get(program.0), self.shaders.get(shader.0)) {
+            self.ctx.attach_shader(&p, &s)
+        }
+    }
+
+    fn detach_shader(&self, program: ProgramId, shader: ShaderId) {
+        if let (Some(p), Some(s)) = (self.programs.get(program.0), self.shaders.get(shader.0)) {
+            self.ctx.detach_shader(&p, &s)
+        }
+    }
+
+    fn link_program(&self, program: ProgramId) {
+        if let Some(p) = self.programs.get(program.0) {
+            self.ctx.link_program(&p)
+        }
+    }
+
+    fn validate_program(&self, program: ProgramId) {
+        if let Some(p) = self.programs.get(program.0) {
+            self.ctx.validate_program(&p)
+        }
+    }
+
+    fn get_program_parameter(&self, program: ProgramId, pname: u32) -> i32 {
+        self.programs
+            .get(program.0)
+            .map(|p| param_to_i32(self.ctx.get_program_parameter(&p, pname)))
+            .unwrap_or(0)
+    }
+
+    fn get_program_info_log(&self, program: ProgramId) -> Option<String> {
+        self.programs.get(program.0).and_then(|p| self.ctx.get_program_info_log(&p))
+    }
+
+    fn use_program(&self, program: Option<ProgramId>) {
+        let p = program.and_then(|p| self.programs.get(p.0));
+        self.ctx.use_program(p.as_deref())
+    }
+
+    fn delete_program(&self, program: ProgramId) {
+        self.location_ids.borrow_mut().retain(|(p, _), loc| {
+            let keep = *p != program.0;
+            if !keep {
+                self.locations.remove(*loc);
+            }
+            keep
+        });
+        self.ctx.delete_program(self.programs.remove(program.0).as_ref())
+    }
+
+    fn get_attrib_location(&self, program: ProgramId, name: &str) -> i32 {
+        self.programs
+            .get(program.0)
+            .map(|p| self.ctx.get_attrib_location(&p, name))
+            .unwrap_or(-1)
+    }
+
+    fn get_uniform_location(&self, program: ProgramId, name: &str) -> Option<UniformLocation> {
+        let key = (program.0, name.to_string());
+        if let Some(&id) = self.location_ids.borrow().get(&key) {
+            return Some(UniformLocation(id));
+        }
+        let loc = self.ctx.get_uniform_location(&*self.programs.get(program.0)?, name)?;
+        let id = self.locations.insert(loc);
+        self.location_ids.borrow_mut().insert(key, id);
+
+        Some(UniformLocation(id))
+    }
+
+    fn get_active_attrib(&self, program: ProgramId, index: u32) -> Option<ActiveInfo> {
+        let info = self.ctx.get_active_attrib(&*self.programs.get(program.0)?, index)?;
+        Some(ActiveInfo { name: info.name(), gl_type: info.type_(), size: info.size() })
+    }
+
+    fn get_active_uniform(&self, program: ProgramId, index: u32) -> Option<ActiveInfo> {
+        let info = self.ctx.get_active_uniform(&*self.programs.get(program.0)?, index)?;
+        Some(ActiveInfo { name: info.name(), gl_type: info.type_(), size: info.size() })
+    }
+
+    fn uniform1i(&self, location: UniformLocation, v: i32) {
+        self.ctx.uniform1i(self.locations.get(location.0).as_deref(), v)
+    }
+
+    fn uniform1f(&self, location: UniformLocation, v: f32) {
+        self.ctx.uniform1f(self.locations.get(location.0).as_deref(), v)
+    }
+
+    fn uniform1iv(&self, location: UniformLocation, v: &[i32]) {
+        self.ctx.uniform1iv_with_i32_array(self.locations.get(location.0).as_deref(), v)
+    }
+
+    fn uniform1fv(&self, location: UniformLocation, v: &[f32]) {
+        self.ctx.uniform1fv_with_f32_array(self.locations.get(location.0).as_deref(), v)
+    }
+
+    fn uniform2fv(&self, location: UniformLocation, v: &[f32]) {
+        self.ctx.uniform2fv_with_f32_array(self.locations.get(location.0).as_deref(), v)
+    }
+
+    fn uniform3fv(&self, location: UniformLocation, v: &[f32]) {
+        self.ctx.uniform3fv_with_f32_array(self.locations.get(location.0).as_deref(), v)
+    }
+
+    fn uniform4fv(&self, location: UniformLocation, v: &[f32]) {
+        self.ctx.uniform4fv_with_f32_array(self.locations.get(location.0).as_deref(), v)
+    }
+
+    fn uniform2iv(&self, location: UniformLocation, v: &[i32]) {
+        self.ctx.uniform2iv_with_i32_array(self.locations.get(location.0).as_deref(), v)
+    }
+
+    fn uniform3iv(&self, location: UniformLocation, v: &[i32]) {
+        self.ctx.uniform3iv_with_i32_array(self.locations.get(location.0).as_deref(), v)
+    }
+
+    fn uniform4iv(&self, location: UniformLocation, v: &[i32]) {
+        self.ctx.uniform4iv_with_i32_array(self.locations.get(location.0).as_deref(), v)
+    }
+
+    fn uniform_matrix2fv(&self, location: UniformLocation, transpose: bool, v: &[f32]) {
+        self.ctx.uniform_matrix2fv_with_f32_array(self.locations.get(location.0).as_deref(), transpose, v)
+    }
+
+    fn uniform_matrix3fv(&self, location: UniformLocation, transpose: bool, v: &[f32]) {
+        self.ctx.uniform_matrix3fv_with_f32_array(self.locations.get(location.0).as_deref(), transpose, v)
+    }
+
+    fn uniform_matrix4fv(&self, location: UniformLocation, transpose: bool, v: &[f32]) {
+        self.ctx.uniform_matrix4fv_with_f32_array(self.locations.get(location.0).as_deref(), transpose, v)
+    }
+
+    fn create_buffer(&self) -> Option<BufferId> {
+        self.ctx.create_buffer().map(|b| BufferId(self.buffers.insert(b)))
+    }
+
+    fn bind_buffer(&self, target: u32, buffer: Option<BufferId>) {
+        let b = buffer.and_then(|b| self.buffers.get(b.0));
+        self.ctx.bind_buffer(target, b.as_deref())
+    }
+
+    fn buffer_data(&self, target: u32, data: &[u8], usage: u32) {
+        self.ctx.buffer_data_with_u8_array(target, data, usage)
+    }
+
+    fn buffer_sub_data(&self, target: u32, offset: i32, data: &[u8]) {
+        self.ctx.buffer_sub_data_with_i32_and_u8_array(target, offset, data)
+    }
+
+    fn delete_buffer(&self, buffer: BufferId) {
+        self.ctx.delete_buffer(self.buffers.remove(buffer.0).as_ref())
+    }
+
+    fn enable_vertex_attrib_array(&self, index: u32) {
+        self.ctx.enable_vertex_attrib_array(index)
+    }
+
+    fn disable_vertex_attrib_array(&self, index: u32) {
+        self.ctx.disable_vertex_attrib_array(index)
+    }
+
+    fn vertex_attrib_pointer(&self, index: u32, size: i32, data_type: u32, normalized: bool, stride: i32, offset: i32) {
+        self.ctx.vertex_attrib_pointer_with_i32(index, size, data_type, normalized, stride, offset)
+    }
+
+    fn draw_elements(&self, mode: u32, count: i32, data_type: u32, offset: i32) {
+        self.ctx.draw_elements_with_i32(mode, count, data_type, offset)
+    }
+
+    fn draw_arrays(&self, mode: u32, first: i32, count: i32) {
+        self.ctx.draw_arrays(mode, first, count)
+    }
+
+    fn vertex_attrib_divisor(&self, index: u32, divisor: u32) {
+        if let Some(ext) = self.instanced_arrays.borrow().as_ref() {
+            ext.vertex_attrib_divisor_angle(index, divisor)
+        }
+    }
+
+    fn draw_elements_instanced(&self, mode: u32, count: i32, data_type: u32, offset: i32, instances: i32) {
+        if let Some(ext) = self.instanced_arrays.borrow().as_ref() {
+            ext.draw_elements_instanced_angle_with_i32(mode, count, data_type, offset, instances)
+        }
+    }
+
+    fn draw_arrays_instanced(&self, mode: u32, first: i32, count: i32, instances: i32) {
+        if let Some(ext) = self.instanced_arrays.borrow().as_ref() {
+            ext.draw_arrays_instanced_angle(mode, first, count, instances)
+        }
+    }
+
+    fn create_texture(&self) -> Option<TextureId> {
+        self.ctx.create_texture().map(|t| TextureId(self.textures.insert(t)))
+    }
+
+    fn active_texture(&self, unit: u32) {
+        self.ctx.active_texture(unit)
+    }
+
+    fn bind_texture(&self, target: u32, texture: Option<TextureId>) {
+        let t = texture.and_then(|t| self.textures.get(t.0));
+        self.ctx.bind_texture(target, t.as_deref())
+    }
+
+    fn tex_image_2d(
+        &self,
+        target: u32,
+        level: i32,
+        internal_format: i32,
+        width: i32,
+        height: i32,
+        format: u32,
+        data_type: u32,
+        data: Option<&[u8]>,
+    ) -> Result<(), String> {
+        // WebGL insists on the typed array matching the pixel type
+        let pixels: Option<js_sys::Object> = data.map(|d| {
+            let bytes = js_sys::Uint8Array::from(d);
+            match data_type {
+                GL::FLOAT => js_sys::Float32Array::new(&bytes.buffer()).into(),
+                GL::UNSIGNED_SHORT
+                | GL::UNSIGNED_SHORT_5_6_5
+                | GL::UNSIGNED_SHORT_4_4_4_4
+                | GL::UNSIGNED_SHORT_5_5_5_1 => js_sys::Uint16Array::new(&bytes.buffer()).into(),
+                GL::UNSIGNED_INT => js_sys::Uint32Array::new(&bytes.buffer()).into(),
+                _ => bytes.into(),
+            }
+        });
+
+        self.ctx
+            .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_array_buffer_view(
+                target,
+                level,
+                internal_format,
+                width,
+                height,
+                0,
+                format,
+                data_type,
+                pixels.as_ref(),
+            )
+            .map_err(|e| format!("{:?}", e))
+    }
+
+    fn tex_parameteri(&self, target: u32, pname: u32, param: i32) {
+        self.ctx.tex_parameteri(target, pname, param)
+    }
+
+    fn delete_texture(&self, texture: TextureId) {
+        self.ctx.delete_texture(self.textures.remove(texture.0).as_ref())
+    }
+
+    fn create_framebuffer(&self) -> Option<FramebufferId> {
+        self.ctx.create_framebuffer().map(|f| FramebufferId(self.framebuffers.insert(f)))
+    }
+
+    fn bind_framebuffer(&self, target: u32, framebuffer: Option<FramebufferId>) {
+        let f = framebuffer.and_then(|f| self.framebuffers.get(f.0));
+        self.ctx.bind_framebuffer(target, f.as_deref())
+    }
+
+    fn framebuffer_texture_2d(&self, target: u32, attachment: u32, tex_target: u32, texture: Option<TextureId>, level: i32) {
+        let t = texture.and_then(|t| self.textures.get(t.0));
+        self.ctx.framebuffer_texture_2d(target, attachment, tex_target, t.as_deref(), level)
+    }
+
+    fn check_framebuffer_status(&self, target: u32) -> u32 {
+        self.ctx.check_framebuffer_status(target)
+    }
+
+    fn delete_framebuffer(&self, framebuffer: FramebufferId) {
+        self.ctx.delete_framebuffer(self.framebuffers.remove(framebuffer.0).as_ref())
+    }
+
+    fn create_vertex_array(&self) -> Option<VertexArrayId> {
+        let ext = self.vertex_array_object.borrow();
+        let vertex_array = ext.as_ref()?.create_vertex_array_oes()?;
+        Some(VertexArrayId(self.vertex_arrays.insert(vertex_array)))
+    }
+
+    fn bind_vertex_array(&self, vertex_array: Option<VertexArrayId>) {
+        if let Some(ext) = self.vertex_array_object.borrow().as_ref() {
+            let v = vertex_array.and_then(|v| self.vertex_arrays.get(v.0));
+            ext.bind_vertex_array_oes(v.as_deref())
+        }
+    }
+
+    fn delete_vertex_array(&self, vertex_array: VertexArrayId) {
+        if let Some(ext) = self.vertex_array_object.borrow().as_ref() {
+            ext.delete_vertex_array_oes(self.vertex_arrays.remove(vertex_array.0).as_ref())
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::{GL, Ctx};
+use crate::backend::{BufferId, FramebufferId, ProgramId, TextureId, VertexArrayId};
+use crate::state::AppliedState;
+
+/// Binding and capability calls that went through the state cache of `Ctx`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Calls forwarded to the backend.
+    pub issued: usize,
+    /// Calls dropped because the value was already set.
+    pub skipped: usize,
+}
+
+/// Last values set through `Ctx`, missing entries are unknown and always forwarded.
+#[derive(Default)]
+pub(crate) struct StateCache {
+    program: Option<Option<ProgramId>>,
+    buffers: HashMap<u32, Option<BufferId>>,
+    active_unit: Option<u32>,
+    textures: HashMap<(u32, u32), Option<TextureId>>,
+    framebuffer: Option<Option<FramebufferId>>,
+    viewport: Option<[i32; 4]>,
+    caps: HashMap<u32, bool>,
+    vertex_array: Option<Option<VertexArrayId>>,
+    pub(crate) render_state: AppliedState,
+    stats: CacheStats,
+}
+
+impl StateCache {
+    fn changed<T: PartialEq>(&mut self, slot: impl FnOnce(&mut Self) -> &mut Option<T>, value: T) -> bool {
+        let slot = slot(self);
+        let changed = slot.as_ref() != Some(&value);
+        *slot = Some(value);
+        self.count(changed)
+    }
+
+    fn changed_in<K: Hash + Eq, V: PartialEq>(&mut self, map: impl FnOnce(&mut Self) -> &mut HashMap<K, V>, key: K, value: V) -> bool {
+        let map = map(self);
+        let changed = map.get(&key) != Some(&value);
+        map.insert(key, value);
+        self.count(changed)
+    }
+
+    fn count(&mut self, changed: bool) -> bool {
+        if changed {
+            self.stats.issued += 1;
+        } else {
+            self.stats.skipped += 1;
+        }
+        changed
+    }
+}
+
+// Inherent methods take precedence over the ones reached through `Deref`,
+// so every call made through `Ctx` is filtered.
+impl Ctx {
+    /// Counters of forwarded and skipped binding / capability calls.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.state_cache.borrow().stats
+    }
+
+    pub fn reset_cache_stats(&self) {
+        self.state_cache.borrow_mut().stats = CacheStats::default();
+    }
+
+    /// Forgets cached bindings, capabilities and applied `RenderState`, needed after GL state was changed
+    /// bypassing `Ctx`, e.g. by another library sharing the context.
+    pub fn reset_state_cache(&self) {
+        let mut cache = self.state_cache.borrow_mut();
+        let stats = cache.stats;
+        *cache = StateCache { stats, ..StateCache::default() };
+    }
+
+    pub fn enable(&self, cap: u32) {
+        if self.state_cache.borrow_mut().changed_in(|c| &mut c.caps, cap, true) {
+            self.backend.enable(cap);
+        }
+    }
+
+    pub fn disable(&self, cap: u32) {
+        if self.state_cache.borrow_mut().changed_in(|c| &mut c.caps, cap, false) {
+            self.backend.disable(cap);
+        }
+    }
+
+    pub fn viewport(&self, x: i32, y: i32, width: i32, height: i32) {
+        if self.state_cache.borrow_mut().changed(|c| &mut c.viewport, [x, y, width, height]) {
+            self.backend.viewport(x, y, width, height);
+        }
+    }
+
+    pub fn use_program(&self, program: Option<ProgramId>) {
+        if self.state_cache.borrow_mut().changed(|c| &mut c.program, program) {
+            self.backend.use_program(program);
+        }
+    }
+
+    pub fn delete_program(&self, program: ProgramId) {
+        // program in use stays current until replaced
+        let mut cache = self.state_cache.borrow_mut();
+        if cache.program == Some(Some(program)) {
+            cache.program = None;
+        }
+        drop(cache);
+        self.backend.delete_program(program);
+    }
+
+    pub fn bind_buffer(&self, target: u32, buffer: Option<BufferId>) {
+        if self.state_cache.borrow_mut().changed_in(|c| &mut c.buffers, target, buffer) {
+            self.backend.bind_buffer(target, buffer);
+        }
+    }
+
+    pub fn delete_buffer(&self, buffer: BufferId) {
+        for bound in self.state_cache.borrow_mut().buffers.values_mut().filter(|b| **b == Some(buffer)) {
+            *bound = None;
+        }
+        self.backend.delete_buffer(buffer);
+    }
+
+    pub fn active_texture(&self, unit: u32) {
+        if self.state_cache.borrow_mut().changed(|c| &mut c.active_unit, unit) {
+            self.backend.active_texture(unit);
+        }
+    }
+
+    pub fn bind_texture(&self, target: u32, texture: Option<TextureId>) {
+        let mut cache = self.state_cache.borrow_mut();
+        let issue = match cache.active_unit {
+            Some(unit) => cache.changed_in(|c| &mut c.textures, (unit, target), texture),
+            None => cache.count(true),
+        };
+        drop(cache);
+        if issue {
+            self.backend.bind_texture(target, texture);
+        }
+    }
+
+    pub fn delete_texture(&self, texture: TextureId) {
+        for bound in self.state_cache.borrow_mut().textures.values_mut().filter(|t| **t == Some(texture)) {
+            *bound = None;
+        }
+        self.backend.delete_texture(texture);
+    }
+
+    pub fn bind_framebuffer(&self, target: u32, framebuffer: Option<FramebufferId>) {
+        let mut cache = self.state_cache.borrow_mut();
+        let issue = if target == GL::FRAMEBUFFER {
+            cache.changed(|c| &mut c.framebuffer, framebuffer)
+        } else {
+            // read and draw bindings are not tracked apart, the cached one may no longer hold
+            cache.framebuffer = None;
+            cache.count(true)
+        };
+        drop(cache);
+        if issue {
+            self.backend.bind_framebuffer(target, framebuffer);
+        }
+    }
+
+    pub fn delete_framebuffer(&self, framebuffer: FramebufferId) {
+        let mut cache = self.state_cache.borrow_mut();
+        if cache.framebuffer == Some(Some(framebuffer)) {
+            cache.framebuffer = Some(None);
+        }
+        drop(cache);
+        self.backend.delete_framebuffer(framebuffer);
+    }
+
+    pub fn bind_vertex_array(&self, vertex_array: Option<VertexArrayId>) {
+        let mut cache = self.state_cache.borrow_mut();
+        if cache.changed(|c| &mut c.vertex_array, vertex_array) {
+            // element array binding belongs to vertex array
+            cache.buffers.remove(&GL::ELEMENT_ARRAY_BUFFER);
+            drop(cache);
+            self.backend.bind_vertex_array(vertex_array);
+        }
+    }
+
+    pub fn delete_vertex_array(&self, vertex_array: VertexArrayId) {
+        let mut cache = self.state_cache.borrow_mut();
+        if cache.vertex_array == Some(Some(vertex_array)) {
+            cache.vertex_array = Some(None);
+            cache.buffers.remove(&GL::ELEMENT_ARRAY_BUFFER);
+        }
+        drop(cache);
+        self.backend.delete_vertex_array(vertex_array);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Pipeline, Program, RenderState, UniformData};
+    use crate::attributes::AttributeVector2;
+    use crate::backend::MockBackend;
+    use crate::backend::mock::Call;
+    use crate::mesh::Mesh;
+    use crate::texture::{ColorFormat, EmptyFramebuffer, TextureSpec, Viewport};
+
+    const DRAW_FRAMEBUFFER: u32 = 0x8CA9;
+
+    fn setup() -> (MockBackend, Ctx) {
+        let mock = MockBackend::new();
+        let ctx = Ctx::with_backend(mock.clone()).unwrap();
+        (mock, ctx)
+    }
+
+    #[test]
+    fn default_render_state_keeps_caps_enabled_by_ctx() {
+        let (mock, ctx) = setup();
+        assert!(mock.is_enabled(GL::DEPTH_TEST) && mock.is_enabled(GL::CULL_FACE));
+        mock.take_calls();
+
+        RenderState::default().apply(&ctx);
+
+        assert!(!mock.take_calls().iter().any(|c| matches!(c, Call::Enable(_))));
+    }
+
+    #[test]
+    fn reset_forgets_applied_render_state() {
+        let (mock, ctx) = setup();
+        RenderState::default().apply(&ctx);
+        mock.take_calls();
+
+        RenderState::default().apply(&ctx);
+        assert!(mock.take_calls().is_empty());
+
+        ctx.reset_state_cache();
+        RenderState::default().apply(&ctx);
+        let calls = mock.take_calls();
+        assert!(calls.contains(&Call::Enable(GL::DEPTH_TEST)));
+        assert!(calls.contains(&Call::DepthFunc(GL::LESS)));
+    }
+
+    #[test]
+    fn other_framebuffer_targets_invalidate_cached_binding() {
+        let (mock, ctx) = setup();
+        let (a, b) = (ctx.create_framebuffer().unwrap(), ctx.create_framebuffer().unwrap());
+        ctx.bind_framebuffer(GL::FRAMEBUFFER, Some(a));
+        ctx.reset_cache_stats();
+
+        ctx.bind_framebuffer(DRAW_FRAMEBUFFER, Some(b));
+        ctx.bind_framebuffer(GL::FRAMEBUFFER, Some(a));
+
+        assert_eq!(ctx.cache_stats(), CacheStats { issued: 2, skipped: 0 });
+        assert_eq!(mock.current_framebuffer(), Some(a));
+    }
+
+    #[test]
+    fn repeated_pass_skips_redundant_bindings() {
+        let (mock, ctx) = setup();
+        let program = Program::new(&ctx, "attribute vec2 position; void main() {}", "uniform sampler2D u_albedo; void main() {}").unwrap();
+        let mut mesh = Mesh::new(&ctx, &[0, 1, 2]).unwrap()
+            .with_attribute::<AttributeVector2>("position", &[[0., 0.], [1., 0.], [0., 1.]]).unwrap();
+        let mut albedo = TextureSpec::new(ColorFormat(GL::RGBA), [1, 1]).upload_u8(&ctx, &[255; 4]).unwrap();
+        let mut output = EmptyFramebuffer::new(&ctx, Viewport::new(4, 4));
+        let mut pipeline = Pipeline::new(&ctx);
+        let mut shade = |ctx: &Ctx| {
+            let skipped = ctx.cache_stats().skipped;
+            mock.take_calls();
+            pipeline.shade(&program, vec![("u_albedo", UniformData::Texture(&mut albedo))], vec![&mut mesh], &mut output).unwrap();
+            (mock.take_calls(), ctx.cache_stats().skipped - skipped)
+        };
+        let redundant = |c: &Call| matches!(
+            c,
+            Call::UseProgram(_) | Call::BindTexture(..) | Call::Viewport(..) | Call::BindFramebuffer(..)
+        );
+
+        let (first, _) = shade(&ctx);
+        assert_eq!(first.iter().filter(|c| redundant(c)).count(), 4);
+
+        let (second, skipped) = shade(&ctx);
+        assert!(!second.iter().any(redundant));
+        assert!(skipped >= 4);
+
+        ctx.reset_state_cache();
+        let (reset, _) = shade(&ctx);
+        assert_eq!(reset.iter().filter(|c| redundant(c)).count(), 4);
+    }
+}
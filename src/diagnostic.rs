@@ -0,0 +1,171 @@
+use std::fmt::Write;
+use std::ops::Range;
+
+use crate::error::ShaderStage;
+
+/// Single entry of shader info log.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    /// 1-based line in the user supplied source, `None` if log entry has no line or points into preamble.
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+/// Lines of context shown around each offending line.
+const CONTEXT: usize = 2;
+
+/// Splits driver log into entries, understands ANGLE / Chrome (`ERROR: 0:17: msg`),
+/// Mesa (`0:17(5): error: msg`) and NVIDIA (`0(17) : error C0000: msg`) formats.
+/// `preamble` is the range of compiled source lines that were inserted into user source.
+pub fn parse_log(log: &str, preamble: Range<usize>) -> Vec<Diagnostic> {
+    log.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && *l != "\0")
+        .map(|l| match parse_line(l) {
+            Some((line, message)) if preamble.contains(&line) => Diagnostic {
+                line: None,
+                message: format!("preamble line {}: {}", line + 1 - preamble.start, message),
+            },
+            Some((line, message)) => Diagnostic {
+                line: match line {
+                    0 => None,
+                    l if l < preamble.start => Some(l),
+                    l => Some(l - preamble.len()),
+                },
+                message,
+            },
+            None => Diagnostic { line: None, message: l.to_string() },
+        })
+        .collect()
+}
+
+fn parse_line(entry: &str) -> Option<(usize, String)> {
+    let rest = ["ERROR:", "WARNING:"]
+        .iter()
+        .find_map(|p| entry.strip_prefix(p))
+        .unwrap_or(entry)
+        .trim_start();
+
+    let (file, tail) = rest.split_at(rest.find(|c: char| !c.is_ascii_digit())?);
+    if file.is_empty() {
+        return None;
+    }
+    let (line, message) = if let Some(tail) = tail.strip_prefix(':') {
+        let (line, message) = tail.split_once(':')?;
+        (line.split('(').next()?, message)
+    } else if let Some(tail) = tail.strip_prefix('(') {
+        tail.split_once(')')?
+    } else {
+        return None;
+    };
+
+    let message = message.trim_start_matches(|c: char| c == ':' || c.is_whitespace());
+    Some((line.trim().parse().ok()?, message.trim_end_matches('\0').to_string()))
+}
+
+/// Renders offending source lines with a few lines of context and log messages underneath.
+pub fn annotate(stage: ShaderStage, source: &str, diagnostics: &[Diagnostic]) -> String {
+    let lines = source.lines().collect::<Vec<_>>();
+    let width = lines.len().to_string().len();
+    let mut out = String::new();
+    let _ = writeln!(out, "{} shader:", stage);
+
+    let mut located = diagnostics
+        .iter()
+        .filter_map(|d| d.line.filter(|l| *l <= lines.len()).map(|l| (l, d)))
+        .collect::<Vec<_>>();
+    located.sort_by_key(|(l, _)| *l);
+
+    let mut shown_until = 0;
+    for (i, (line, _)) in located.iter().enumerate() {
+        if located[..i].iter().any(|(l, _)| l == line) {
+            continue;
+        }
+        let from = line.saturating_sub(CONTEXT).max(shown_until + 1).max(1);
+        let to = (line + CONTEXT).min(lines.len());
+        if from > shown_until + 1 && shown_until > 0 {
+            let _ = writeln!(out, "{:>w$} |", "...", w = width + 2);
+        }
+        for n in from..=to {
+            let marker = if located.iter().any(|(l, _)| *l == n) { '>' } else { ' ' };
+            let _ = writeln!(out, "{} {:>w$} | {}", marker, n, lines.get(n - 1).unwrap_or(&""), w = width);
+            for (_, d) in located.iter().filter(|(l, _)| *l == n) {
+                let _ = writeln!(out, "  {:>w$} | ^ {}", "", d.message, w = width);
+            }
+        }
+        shown_until = shown_until.max(to);
+    }
+
+    for d in diagnostics.iter().filter(|d| d.line.map(|l| l > lines.len()).unwrap_or(true)) {
+        match d.line {
+            Some(l) => {
+                let _ = writeln!(out, "  line {}: {}", l, d.message);
+            }
+            None => {
+                let _ = writeln!(out, "  {}", d.message);
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Program;
+
+    fn located(line: usize, message: &str) -> Diagnostic {
+        Diagnostic { line: Some(line), message: message.to_string() }
+    }
+
+    #[test]
+    fn driver_log_formats_are_parsed() {
+        let log = "ERROR: 0:12: 'x' : undeclared identifier\n\
+                   0:12(3): error: syntax error, unexpected '}'\n\
+                   0(12) : error C0000: syntax error\n\
+                   ERROR: 1 compilation errors.  No code generated.\n\0";
+
+        assert_eq!(parse_log(log, 1..1), vec![
+            located(12, "'x' : undeclared identifier"),
+            located(12, "error: syntax error, unexpected '}'"),
+            located(12, "error C0000: syntax error"),
+            Diagnostic { line: None, message: "ERROR: 1 compilation errors.  No code generated.".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn lines_are_mapped_around_preamble() {
+        let (source, preamble) = Program::prepend("#define A\n#define B", "void main() {}\n");
+        assert_eq!(source, "#define A\n#define B\nvoid main() {}\n");
+        assert_eq!(parse_log("ERROR: 0:3: a\nERROR: 0:2: b", preamble), vec![
+            located(1, "a"),
+            Diagnostic { line: None, message: "preamble line 2: b".to_string() },
+        ]);
+
+        let (source, preamble) = Program::prepend("#define A", "#version 100\nvoid main() {}\n");
+        assert_eq!(source, "#version 100\n#define A\nvoid main() {}\n");
+        assert_eq!(parse_log("ERROR: 0:1: a\nERROR: 0:2: b\nERROR: 0:3: c", preamble), vec![
+            located(1, "a"),
+            Diagnostic { line: None, message: "preamble line 1: b".to_string() },
+            located(2, "c"),
+        ]);
+    }
+
+    #[test]
+    fn excerpt_marks_offending_lines() {
+        let source = "#version 100\nuniform vec4 u_color;\nvoid main() {\n    gl_FragColor = color;\n}\n";
+        let (_, preamble) = Program::prepend("#define LIGHTS 4", source);
+        let diagnostics = parse_log("ERROR: 0:5: 'color' : undeclared identifier\nERROR: 0:2: bad define", preamble);
+
+        assert_eq!(annotate(ShaderStage::Fragment, source, &diagnostics), "\
+fragment shader:
+  2 | uniform vec4 u_color;
+  3 | void main() {
+> 4 |     gl_FragColor = color;
+    | ^ 'color' : undeclared identifier
+  5 | }
+  preamble line 1: bad define
+");
+    }
+}
@@ -0,0 +1,167 @@
+use std::fmt;
+use std::ops::Range;
+
+use wasm_bindgen::JsValue;
+
+use crate::GL;
+use crate::diagnostic::{annotate, parse_log, Diagnostic};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+}
+
+impl ShaderStage {
+    pub fn from_gl(shader_type: u32) -> Self {
+        match shader_type {
+            GL::VERTEX_SHADER => ShaderStage::Vertex,
+            _ => ShaderStage::Fragment,
+        }
+    }
+}
+
+impl From<ShaderStage> for u32 {
+    fn from(stage: ShaderStage) -> Self {
+        match stage {
+            ShaderStage::Vertex => GL::VERTEX_SHADER,
+            ShaderStage::Fragment => GL::FRAGMENT_SHADER,
+        }
+    }
+}
+
+impl fmt::Display for ShaderStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderStage::Vertex => write!(f, "vertex"),
+            ShaderStage::Fragment => write!(f, "fragment"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResourceKind {
+    Buffer,
+    Shader,
+    Program,
+    Texture,
+    Framebuffer,
+    VertexArray,
+}
+
+impl fmt::Display for ResourceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResourceKind::Buffer => write!(f, "buffer"),
+            ResourceKind::Shader => write!(f, "shader"),
+            ResourceKind::Program => write!(f, "program"),
+            ResourceKind::Texture => write!(f, "texture"),
+            ResourceKind::Framebuffer => write!(f, "framebuffer"),
+            ResourceKind::VertexArray => write!(f, "vertex array"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GraphError {
+    /// Texture is the output of more than one pass.
+    MultipleWriters(&'static str),
+    /// Texture is read by a pass but no pass writes it.
+    NotWritten(&'static str),
+    /// Imported texture is used as pass output.
+    ReadOnly(&'static str),
+    FeedbackLoop(&'static str),
+    Cycle(&'static str),
+    /// Pass asked for a texture it did not declare, or took it twice.
+    UnknownInput(&'static str),
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphError::MultipleWriters(texture) => write!(f, "texture {} is written by more than one pass", texture),
+            GraphError::NotWritten(texture) => write!(f, "texture {} is read but never written", texture),
+            GraphError::ReadOnly(texture) => write!(f, "imported texture {} can't be written", texture),
+            GraphError::FeedbackLoop(pass) => write!(f, "pass {} reads its own output", pass),
+            GraphError::Cycle(pass) => write!(f, "pass {} depends on itself", pass),
+            GraphError::UnknownInput(pass) => write!(f, "pass {} has no such input left", pass),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    /// Canvas or rendering context could not be obtained.
+    Context(String),
+    ShaderCompile {
+        stage: ShaderStage,
+        log: String,
+        /// Source line the first error in `log` points to, if any.
+        source_line: Option<String>,
+        diagnostics: Vec<Diagnostic>,
+        /// Offending source lines annotated with log messages.
+        excerpt: String,
+    },
+    ProgramLink(String),
+    ProgramValidate(String),
+    MissingExtension(&'static str),
+    ResourceCreation(ResourceKind),
+    TextureUpload(String),
+    BufferUpdate(String),
+    IncompleteFramebuffer(u32),
+    ContextLost,
+    /// Strict mode: uniform is not used by the program.
+    UnknownUniform(String),
+    /// Strict mode: attribute used by the program is not provided by the mesh.
+    MissingAttribute(String),
+    /// Strict mode: value does not match GLSL type of the declaration.
+    TypeMismatch { name: String, expected: &'static str, found: &'static str },
+    UnknownSubmesh(usize),
+    RenderGraph(GraphError),
+}
+
+impl Error {
+    /// `source` is the shader as written by user, `preamble` is the range of lines inserted into it.
+    pub(crate) fn shader_compile(stage: ShaderStage, log: String, source: &str, preamble: Range<usize>) -> Self {
+        let diagnostics = parse_log(&log, preamble);
+        let source_line = diagnostics
+            .iter()
+            .find_map(|d| d.line)
+            .and_then(|n| source.lines().nth(n - 1))
+            .map(|l| l.to_string());
+        let excerpt = annotate(stage, source, &diagnostics);
+
+        Error::ShaderCompile { stage, log, source_line, diagnostics, excerpt }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Context(msg) => write!(f, "Failed to get context {}", msg),
+            Error::ShaderCompile { excerpt, .. } => write!(f, "Failed to compile {}", excerpt.trim_end()),
+            Error::ProgramLink(log) => write!(f, "Failed to link program {}", log),
+            Error::ProgramValidate(log) => write!(f, "Failed to validate program {}", log),
+            Error::MissingExtension(name) => write!(f, "Extension {} is not available", name),
+            Error::ResourceCreation(kind) => write!(f, "Failed to create {}", kind),
+            Error::TextureUpload(msg) => write!(f, "Failed to send image data {}", msg),
+            Error::BufferUpdate(msg) => write!(f, "Failed to update buffer, {}", msg),
+            Error::IncompleteFramebuffer(status) => write!(f, "Framebuffer is incomplete, status {:#x}", status),
+            Error::ContextLost => write!(f, "Context lost"),
+            Error::UnknownUniform(name) => write!(f, "Program has no active uniform {}", name),
+            Error::MissingAttribute(name) => write!(f, "Mesh does not provide attribute {}", name),
+            Error::TypeMismatch { name, expected, found } =>
+                write!(f, "{} is declared as {} but {} was given", name, expected, found),
+            Error::UnknownSubmesh(index) => write!(f, "Mesh has no submesh {}", index),
+            Error::RenderGraph(e) => write!(f, "Invalid render graph, {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for JsValue {
+    fn from(e: Error) -> Self {
+        JsValue::from_str(&e.to_string())
+    }
+}
@@ -0,0 +1,494 @@
+use crate::{Ctx, Error};
+use crate::error::GraphError;
+use crate::texture::*;
+
+/// Texture declared in a `RenderGraph`, valid only for the graph that created it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GraphTexture(usize);
+
+/// What a pass renders into, either a graph texture or the screen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PassOutput {
+    Texture(GraphTexture),
+    Screen(Viewport),
+}
+
+impl From<GraphTexture> for PassOutput {
+    fn from(texture: GraphTexture) -> Self {
+        PassOutput::Texture(texture)
+    }
+}
+
+enum Source<'a> {
+    Transient(TextureSpec, InternalFormat),
+    Imported(&'a mut UploadedTexture),
+}
+
+struct TextureNode<'a> {
+    name: &'static str,
+    source: Source<'a>,
+}
+
+type PassFn<'a> = Box<dyn FnOnce(&mut PassContext<'_>) -> Result<(), Error> + 'a>;
+
+struct PassNode<'a> {
+    name: &'static str,
+    inputs: Vec<GraphTexture>,
+    output: PassOutput,
+    execute: PassFn<'a>,
+}
+
+/// Passes of one frame, ordered by the textures they read and write.
+///
+/// Passes not contributing to the screen are culled, intermediate textures are taken from
+/// `TargetPool` and returned to it once their last reader ran.
+#[derive(Default)]
+pub struct RenderGraph<'a> {
+    textures: Vec<TextureNode<'a>>,
+    passes: Vec<PassNode<'a>>,
+}
+
+impl<'a> RenderGraph<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declares intermediate texture, backing storage is allocated on execution. Pooled framebuffers
+    /// have a color attachment only, passes rendering into them get no depth buffer, so depth test
+    /// has no effect there and draw order decides what ends up on top.
+    pub fn create_texture(&mut self, name: &'static str, spec: TextureSpec, internal_format: InternalFormat) -> GraphTexture {
+        self.add_texture(name, Source::Transient(spec, internal_format))
+    }
+
+    /// Makes texture owned elsewhere readable by passes.
+    pub fn import_texture(&mut self, name: &'static str, texture: &'a mut UploadedTexture) -> GraphTexture {
+        self.add_texture(name, Source::Imported(texture))
+    }
+
+    pub fn add_pass<O, F>(&mut self, name: &'static str, inputs: &[GraphTexture], output: O, execute: F) where
+        O: Into<PassOutput>,
+        F: FnOnce(&mut PassContext<'_>) -> Result<(), Error> + 'a,
+    {
+        let mut unique = Vec::with_capacity(inputs.len());
+        for &input in inputs {
+            if !unique.contains(&input) {
+                unique.push(input);
+            }
+        }
+
+        self.passes.push(PassNode {
+            name,
+            inputs: unique,
+            output: output.into(),
+            execute: Box::new(execute),
+        });
+    }
+
+    /// Names of passes in the order they would run, culled passes left out.
+    pub fn schedule(&self) -> Result<Vec<&'static str>, Error> {
+        Ok(self.compile()?.into_iter().map(|p| self.passes[p].name).collect())
+    }
+
+    pub fn execute(self, pool: &mut TargetPool) -> Result<(), Error> {
+        let order = self.compile()?;
+        let RenderGraph { mut textures, passes } = self;
+
+        let mut last_read = vec![0; textures.len()];
+        for (step, &p) in order.iter().enumerate() {
+            for input in &passes[p].inputs {
+                last_read[input.0] = step;
+            }
+        }
+
+        let mut passes = passes.into_iter().map(Some).collect::<Vec<_>>();
+        let mut slots = vec![None; textures.len()];
+        pool.begin();
+
+        for (step, &p) in order.iter().enumerate() {
+            let pass = passes[p].take().expect("pass is scheduled once");
+
+            let (output_slot, target) = match pass.output {
+                PassOutput::Screen(viewport) => (None, Target::Screen(EmptyFramebuffer::new(&pool.ctx, viewport))),
+                PassOutput::Texture(texture) => match textures[texture.0].source {
+                    Source::Transient(spec, internal_format) => {
+                        let slot = pool.acquire(spec, internal_format)?;
+                        slots[texture.0] = Some(slot);
+                        (Some(slot), Target::Texture(pool.take(slot)))
+                    }
+                    Source::Imported(_) => return Err(Error::RenderGraph(GraphError::ReadOnly(textures[texture.0].name))),
+                },
+            };
+
+            let mut taken = pass.inputs
+                .iter()
+                .filter_map(|&input| slots[input.0].map(|slot| (input, slot, pool.take(slot))))
+                .collect::<Vec<_>>();
+
+            let mut inputs = textures
+                .iter_mut()
+                .enumerate()
+                .filter(|(i, _)| pass.inputs.contains(&GraphTexture(*i)))
+                .filter_map(|(i, node)| match &mut node.source {
+                    Source::Imported(texture) => Some((GraphTexture(i), Some(&mut **texture))),
+                    Source::Transient(..) => None,
+                })
+                .collect::<Vec<_>>();
+            inputs.extend(taken.iter_mut().map(|(input, _, fb)| (*input, Some(fb.color_slot()))));
+
+            let mut context = PassContext { name: pass.name, inputs, target: GraphTarget(target) };
+            let result = (pass.execute)(&mut context);
+            let PassContext { target, .. } = context;
+
+            if let (Some(slot), Target::Texture(fb)) = (output_slot, target.0) {
+                pool.put(slot, fb);
+            }
+            for (_, slot, fb) in taken {
+                pool.put(slot, fb);
+            }
+            result?;
+
+            for input in &pass.inputs {
+                if let (Some(slot), true) = (slots[input.0], last_read[input.0] == step) {
+                    pool.release(slot);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn add_texture(&mut self, name: &'static str, source: Source<'a>) -> GraphTexture {
+        self.textures.push(TextureNode { name, source });
+        GraphTexture(self.textures.len() - 1)
+    }
+
+    /// Indices of passes reachable from screen output, sorted so that writers run before readers,
+    /// otherwise keeping declaration order.
+    fn compile(&self) -> Result<Vec<usize>, Error> {
+        let mut writers = vec![None; self.textures.len()];
+        for (p, pass) in self.passes.iter().enumerate() {
+            if let PassOutput::Texture(texture) = pass.output {
+                let node = &self.textures[texture.0];
+                if let Source::Imported(_) = node.source {
+                    return Err(Error::RenderGraph(GraphError::ReadOnly(node.name)));
+                }
+                if pass.inputs.contains(&texture) {
+                    return Err(Error::RenderGraph(GraphError::FeedbackLoop(pass.name)));
+                }
+                if writers[texture.0].replace(p).is_some() {
+                    return Err(Error::RenderGraph(GraphError::MultipleWriters(node.name)));
+                }
+            }
+        }
+
+        let mut live = vec![false; self.passes.len()];
+        let mut stack = self.passes
+            .iter()
+            .enumerate()
+            .filter(|(_, pass)| matches!(pass.output, PassOutput::Screen(_)))
+            .map(|(p, _)| p)
+            .collect::<Vec<_>>();
+        let mut deps = vec![Vec::new(); self.passes.len()];
+
+        while let Some(p) = stack.pop() {
+            if live[p] {
+                continue;
+            }
+            live[p] = true;
+
+            for input in &self.passes[p].inputs {
+                let node = &self.textures[input.0];
+                if let Source::Imported(_) = node.source {
+                    continue;
+                }
+                let writer = writers[input.0].ok_or(Error::RenderGraph(GraphError::NotWritten(node.name)))?;
+                deps[p].push(writer);
+                stack.push(writer);
+            }
+        }
+
+        // screen passes draw over each other in declaration order
+        let screen = (0..self.passes.len())
+            .filter(|&p| live[p] && matches!(self.passes[p].output, PassOutput::Screen(_)))
+            .collect::<Vec<_>>();
+        for pair in screen.windows(2) {
+            deps[pair[1]].push(pair[0]);
+        }
+
+        let count = live.iter().filter(|l| **l).count();
+        let mut done = vec![false; self.passes.len()];
+        let mut order = Vec::with_capacity(count);
+        while order.len() < count {
+            let pending = |p: &usize| live[*p] && !done[*p];
+            match (0..self.passes.len()).filter(pending).find(|&p| deps[p].iter().all(|&d| done[d])) {
+                Some(p) => {
+                    done[p] = true;
+                    order.push(p);
+                }
+                None => {
+                    let p = (0..self.passes.len()).find(pending).expect("pending pass");
+                    return Err(Error::RenderGraph(GraphError::Cycle(self.passes[p].name)));
+                }
+            }
+        }
+
+        Ok(order)
+    }
+}
+
+/// Textures and render target handed to a running pass.
+pub struct PassContext<'p> {
+    name: &'static str,
+    inputs: Vec<(GraphTexture, Option<&'p mut UploadedTexture>)>,
+    target: GraphTarget,
+}
+
+impl<'p> PassContext<'p> {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Texture declared as pass input, each input can be taken once.
+    pub fn input(&mut self, texture: GraphTexture) -> Result<&'p mut UploadedTexture, Error> {
+        self.inputs
+            .iter_mut()
+            .find(|(t, _)| *t == texture)
+            .and_then(|(_, slot)| slot.take())
+            .ok_or(Error::RenderGraph(GraphError::UnknownInput(self.name)))
+    }
+
+    pub fn target(&mut self) -> &mut GraphTarget {
+        &mut self.target
+    }
+}
+
+enum Target {
+    Screen(EmptyFramebuffer),
+    Texture(ColorFramebuffer),
+}
+
+/// Output of a pass, to be passed to `Pipeline::shade`.
+pub struct GraphTarget(Target);
+
+impl Framebuffer for GraphTarget {
+    type DepthSlot = Self;
+    type ColorSlot = Self;
+
+    fn depth_slot(&mut self) -> &mut Self { self }
+    fn color_slot(&mut self) -> &mut Self { self }
+
+    fn bind(&mut self) {
+        match &mut self.0 {
+            Target::Screen(fb) => Framebuffer::bind(fb),
+            Target::Texture(fb) => fb.bind(),
+        }
+    }
+
+    fn viewport(&self) -> &Viewport {
+        match &self.0 {
+            Target::Screen(fb) => fb.viewport(),
+            Target::Texture(fb) => fb.viewport(),
+        }
+    }
+}
+
+struct PooledTarget {
+    spec: TextureSpec,
+    internal_format: InternalFormat,
+    framebuffer: Option<ColorFramebuffer>,
+    in_use: bool,
+    used: bool,
+}
+
+/// Color framebuffers backing graph textures, kept between executions so frames reuse them.
+pub struct TargetPool {
+    ctx: Ctx,
+    targets: Vec<PooledTarget>,
+}
+
+impl TargetPool {
+    pub fn new(ctx: &Ctx) -> Self {
+        Self {
+            ctx: ctx.clone(),
+            targets: Vec::new(),
+        }
+    }
+
+    /// Number of allocated framebuffers.
+    pub fn len(&self) -> usize {
+        self.targets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    /// Frees framebuffers the last executed graph did not use.
+    pub fn trim(&mut self) {
+        self.targets.retain(|t| t.used);
+    }
+
+    fn begin(&mut self) {
+        for target in &mut self.targets {
+            target.in_use = false;
+            target.used = false;
+        }
+    }
+
+    fn acquire(&mut self, spec: TextureSpec, internal_format: InternalFormat) -> Result<usize, Error> {
+        let free = self.targets
+            .iter()
+            .position(|t| !t.in_use && t.spec == spec && t.internal_format == internal_format);
+
+        let slot = match free {
+            Some(slot) => slot,
+            None => {
+                let texture = spec.upload(&self.ctx, internal_format, None)?;
+                let [w, h] = spec.dimensions;
+                let framebuffer = EmptyFramebuffer::new(&self.ctx, Viewport::new(w, h)).with_color_slot(texture)?;
+                self.targets.push(PooledTarget {
+                    spec,
+                    internal_format,
+                    framebuffer: Some(framebuffer),
+                    in_use: false,
+                    used: false,
+                });
+                self.targets.len() - 1
+            }
+        };
+
+        let target = &mut self.targets[slot];
+        target.in_use = true;
+        target.used = true;
+        Ok(slot)
+    }
+
+    fn release(&mut self, slot: usize) {
+        self.targets[slot].in_use = false;
+    }
+
+    fn take(&mut self, slot: usize) -> ColorFramebuffer {
+        self.targets[slot].framebuffer.take().expect("pooled framebuffer is in use by one pass only")
+    }
+
+    fn put(&mut self, slot: usize, framebuffer: ColorFramebuffer) {
+        self.targets[slot].framebuffer = Some(framebuffer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GL;
+    use crate::backend::MockBackend;
+
+    fn spec() -> TextureSpec {
+        TextureSpec::new(ColorFormat(GL::RGBA), [4, 4])
+    }
+
+    fn screen() -> PassOutput {
+        PassOutput::Screen(Viewport::new(4, 4))
+    }
+
+    fn noop(_: &mut PassContext<'_>) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn error(graph: &RenderGraph<'_>) -> Option<GraphError> {
+        match graph.schedule() {
+            Err(Error::RenderGraph(e)) => Some(e),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn passes_not_reaching_screen_are_culled() {
+        let mut graph = RenderGraph::new();
+        let scene = graph.create_texture("scene", spec(), InternalFormat(GL::RGBA));
+        let debug = graph.create_texture("debug", spec(), InternalFormat(GL::RGBA));
+        graph.add_pass("scene", &[], scene, noop);
+        graph.add_pass("debug", &[scene], debug, noop);
+        graph.add_pass("present", &[scene], screen(), noop);
+
+        assert_eq!(graph.schedule(), Ok(vec!["scene", "present"]));
+    }
+
+    #[test]
+    fn writers_run_before_readers_whatever_declaration_order() {
+        let mut graph = RenderGraph::new();
+        let scene = graph.create_texture("scene", spec(), InternalFormat(GL::RGBA));
+        let blurred = graph.create_texture("blurred", spec(), InternalFormat(GL::RGBA));
+        graph.add_pass("present", &[blurred, scene], screen(), noop);
+        graph.add_pass("blur", &[scene], blurred, noop);
+        graph.add_pass("scene", &[], scene, noop);
+
+        assert_eq!(graph.schedule(), Ok(vec!["scene", "blur", "present"]));
+    }
+
+    #[test]
+    fn invalid_graphs_are_rejected() {
+        let mut graph = RenderGraph::new();
+        let scene = graph.create_texture("scene", spec(), InternalFormat(GL::RGBA));
+        graph.add_pass("opaque", &[], scene, noop);
+        graph.add_pass("transparent", &[], scene, noop);
+        graph.add_pass("present", &[scene], screen(), noop);
+        assert_eq!(error(&graph), Some(GraphError::MultipleWriters("scene")));
+
+        let mut graph = RenderGraph::new();
+        let scene = graph.create_texture("scene", spec(), InternalFormat(GL::RGBA));
+        graph.add_pass("accumulate", &[scene], scene, noop);
+        graph.add_pass("present", &[scene], screen(), noop);
+        assert_eq!(error(&graph), Some(GraphError::FeedbackLoop("accumulate")));
+
+        let mut graph = RenderGraph::new();
+        let ping = graph.create_texture("ping", spec(), InternalFormat(GL::RGBA));
+        let pong = graph.create_texture("pong", spec(), InternalFormat(GL::RGBA));
+        graph.add_pass("a", &[pong], ping, noop);
+        graph.add_pass("b", &[ping], pong, noop);
+        graph.add_pass("present", &[ping], screen(), noop);
+        assert_eq!(error(&graph), Some(GraphError::Cycle("a")));
+
+        let mut graph = RenderGraph::new();
+        let scene = graph.create_texture("scene", spec(), InternalFormat(GL::RGBA));
+        graph.add_pass("present", &[scene], screen(), noop);
+        assert_eq!(error(&graph), Some(GraphError::NotWritten("scene")));
+
+        let ctx = Ctx::with_backend(MockBackend::new()).unwrap();
+        let mut noise = spec().upload(&ctx, InternalFormat(GL::RGBA), None).unwrap();
+        let mut graph = RenderGraph::new();
+        let noise = graph.import_texture("noise", &mut noise);
+        graph.add_pass("generate", &[], noise, noop);
+        graph.add_pass("present", &[noise], screen(), noop);
+        assert_eq!(error(&graph), Some(GraphError::ReadOnly("noise")));
+    }
+
+    /// Three same sized textures written one after another, each read only by the next pass.
+    fn chain<'a>() -> RenderGraph<'a> {
+        let mut graph = RenderGraph::new();
+        let textures = ["a", "b", "c"].map(|name| graph.create_texture(name, spec(), InternalFormat(GL::RGBA)));
+        graph.add_pass("a", &[], textures[0], noop);
+        for (&pass, pair) in ["b", "c"].iter().zip(textures.windows(2)) {
+            let input = pair[0];
+            graph.add_pass(pass, &[input], pair[1], move |p| p.input(input).map(|_| ()));
+        }
+        let input = textures[2];
+        graph.add_pass("present", &[input], screen(), move |p| p.input(input).map(|_| ()));
+        graph
+    }
+
+    #[test]
+    fn pool_reuses_targets_within_and_across_executions() {
+        let mock = MockBackend::new();
+        let ctx = Ctx::with_backend(mock.clone()).unwrap();
+        let mut pool = TargetPool::new(&ctx);
+
+        chain().execute(&mut pool).unwrap();
+        // "a" is released once "b" read it, so "c" renders into its framebuffer
+        assert_eq!(pool.len(), 2);
+        let framebuffers = mock.live_framebuffers();
+
+        chain().execute(&mut pool).unwrap();
+        assert_eq!(pool.len(), 2);
+        assert_eq!(mock.live_framebuffers(), framebuffers);
+    }
+}
@@ -1,38 +1,103 @@
 use mesh::Mesh;
 use util::get_ctx;
-use std::{rc::Rc, ops::Deref};
+use std::{fmt, rc::Rc, cell::RefCell, collections::HashMap, ops::{Deref, Range}};
 use web_sys::*;
 
 pub mod mesh;
 pub mod texture;
 pub mod util;
 pub mod attributes;
+pub mod backend;
+pub mod error;
+pub mod diagnostic;
+pub mod reflection;
+pub mod uniforms;
+pub mod state;
+pub mod cache;
+pub mod graph;
+
+pub use crate::error::Error;
+pub use crate::uniforms::{AsUniformData, Uniforms};
+pub use crate::state::RenderState;
+#[cfg(feature = "derive")]
+pub use glsmrs_derive::Uniforms;
 
 use crate::texture::*;
+use crate::backend::{GlBackend, ProgramId, ShaderId, UniformLocation, WebBackend};
+use crate::error::{ResourceKind, ShaderStage};
+use crate::reflection::{ActiveAttribute, ActiveUniform};
+use crate::cache::StateCache;
 
 #[derive(Clone)]
-pub struct Ctx(Rc<WebGlRenderingContext>);
+pub struct Ctx {
+    backend: Rc<dyn GlBackend>,
+    state_cache: Rc<RefCell<StateCache>>,
+    extensions: Rc<RefCell<HashMap<&'static str, bool>>>,
+}
 
 impl Ctx {
-    pub fn from(canvas_name: &str) -> Result<Self, String> {
-        let ctx = get_ctx(canvas_name, "webgl").map_err(|e| format!("{:?}", e))?;
+    pub fn from(canvas_name: &str) -> Result<Self, Error> {
+        let ctx = get_ctx(canvas_name, "webgl").map_err(|e| Error::Context(format!("{:?}", e)))?;
         Self::new(ctx)
     }
-    pub fn new(ctx: WebGlRenderingContext) -> Result<Self, String> {
-        ctx.get_extension("WEBGL_depth_texture").map_err(|e| format!("no depth textures available {:?}", e))?;
-        ctx.get_extension("OES_texture_float").map_err(|e| format!("no float textures available {:?}", e))?;
+
+    pub fn new(ctx: WebGlRenderingContext) -> Result<Self, Error> {
+        Self::with_backend(WebBackend::new(ctx))
+    }
+
+    pub fn with_backend<B: GlBackend + 'static>(backend: B) -> Result<Self, Error> {
+        for ext in ["WEBGL_depth_texture", "OES_texture_float"] {
+            if !backend.get_extension(ext) {
+                return Err(Error::MissingExtension(ext));
+            }
+        }
+
+        let ctx = Self {
+            backend: Rc::new(backend),
+            state_cache: Rc::new(RefCell::new(StateCache::default())),
+            extensions: Rc::new(RefCell::new(HashMap::new())),
+        };
+        // same as `RenderState` default, set through the state cache so it is recorded there
         ctx.enable(GL::DEPTH_TEST);
         ctx.enable(GL::CULL_FACE);
 
-        Ok(Self(Rc::new(ctx)))
+        Ok(ctx)
+    }
+
+    /// Enables optional extension, WebGL extensions become active once queried.
+    pub fn require_extension(&self, name: &'static str) -> Result<(), Error> {
+        if self.has_extension(name) {
+            Ok(())
+        } else {
+            Err(Error::MissingExtension(name))
+        }
+    }
+
+    /// Same as `get_extension`, but the backend is asked only once per extension.
+    pub fn has_extension(&self, name: &'static str) -> bool {
+        if let Some(available) = self.extensions.borrow().get(name) {
+            return *available;
+        }
+        let available = self.get_extension(name);
+        self.extensions.borrow_mut().insert(name, available);
+        available
+    }
+
+    /// Turns missing handle into error, telling lost context apart from other failures.
+    pub(crate) fn resource<T>(&self, handle: Option<T>, kind: ResourceKind) -> Result<T, Error> {
+        match handle {
+            Some(h) => Ok(h),
+            None if self.is_context_lost() => Err(Error::ContextLost),
+            None => Err(Error::ResourceCreation(kind)),
+        }
     }
 }
 
 impl Deref for Ctx {
-    type Target = WebGlRenderingContext;
+    type Target = dyn GlBackend;
 
-    fn deref(&self) -> &WebGlRenderingContext {
-        &self.0
+    fn deref(&self) -> &Self::Target {
+        &*self.backend
     }
 }
 
@@ -40,57 +105,319 @@ pub type GL = WebGlRenderingContext;
 
 pub struct Program {
     ctx: Ctx,
-    program: WebGlProgram,
+    program: ProgramId,
+    attributes: Vec<ActiveAttribute>,
+    uniforms: Vec<ActiveUniform>,
+    // resolved once, names missing from reflection (e.g. `lights[2]`) are queried on first use
+    attribute_locations: RefCell<HashMap<String, Option<u32>>>,
+    uniform_locations: RefCell<HashMap<String, Option<UniformLocation>>>,
+    strict: bool,
+    // meshes keep a weak reference next to vertex arrays recorded for this program,
+    // so a program created later with the same id is not mistaken for it
+    identity: Rc<()>,
 }
 
 impl Program {
-    pub fn new(ctx: &Ctx, vertex: &str, fragment: &str) -> Result<Program, String> {
-        let vertex_id = Program::shader(ctx, GL::VERTEX_SHADER, vertex)?;
-        let fragment_id = Program::shader(ctx, GL::FRAGMENT_SHADER, fragment)?;
+    pub fn new(ctx: &Ctx, vertex: &str, fragment: &str) -> Result<Program, Error> {
+        Self::with_preamble(ctx, "", vertex, fragment)
+    }
 
-        let program = ctx.create_program().ok_or("Failed to create program")?;
-        ctx.attach_shader(&program, &vertex_id);
-        ctx.attach_shader(&program, &fragment_id);
-        ctx.link_program(&program);
+    /// Compiles both shaders with `preamble` (e.g. a list of `#define`s) inserted right after
+    /// `#version` directive if there is one, compile errors still refer to lines of original sources.
+    pub fn with_preamble(ctx: &Ctx, preamble: &str, vertex: &str, fragment: &str) -> Result<Program, Error> {
+        let vertex_id = Program::shader(ctx, ShaderStage::Vertex, preamble, vertex)?;
+        let fragment_id = Program::shader(ctx, ShaderStage::Fragment, preamble, fragment)
+            .inspect_err(|_| ctx.delete_shader(vertex_id))?;
 
-        Ok(Program {
+        let program = ctx.create_program();
+        if let Some(program) = program {
+            ctx.attach_shader(program, vertex_id);
+            ctx.attach_shader(program, fragment_id);
+            ctx.link_program(program);
+            // shaders are no longer needed once program is linked
+            ctx.detach_shader(program, vertex_id);
+            ctx.detach_shader(program, fragment_id);
+        }
+        ctx.delete_shader(vertex_id);
+        ctx.delete_shader(fragment_id);
+
+        let mut program = Program {
             ctx: ctx.clone(),
-            program,
-        })
-    }
-
-    fn shader(ctx: &Ctx, shader_type: u32, source: &str) -> Result<WebGlShader, String> {
-        let shader = ctx
-            .create_shader(shader_type)
-            .ok_or(format!("Failed to create shader {}", shader_type))?;
-        ctx.shader_source(&shader, source);
-        ctx.compile_shader(&shader);
-
-        if ctx
-            .get_shader_parameter(&shader, GL::COMPILE_STATUS)
-            .as_bool()
-            .unwrap_or(false)
-        {
+            program: ctx.resource(program, ResourceKind::Program)?,
+            attributes: vec![],
+            uniforms: vec![],
+            attribute_locations: RefCell::new(HashMap::new()),
+            uniform_locations: RefCell::new(HashMap::new()),
+            strict: false,
+            identity: Rc::new(()),
+        };
+
+        if ctx.get_program_parameter(program.program, GL::LINK_STATUS) == 0 {
+            return Err(Error::ProgramLink(program.info_log()));
+        }
+        program.attributes = reflection::attributes(ctx, program.program);
+        program.uniforms = reflection::uniforms(ctx, program.program);
+        program.attribute_locations = RefCell::new(
+            program.attributes.iter().map(|a| (a.name.clone(), Some(a.location))).collect()
+        );
+        program.uniform_locations = RefCell::new(
+            program.uniforms.iter().map(|u| (u.name.clone(), Some(u.location))).collect()
+        );
+
+        Ok(program)
+    }
+
+    fn info_log(&self) -> String {
+        self.ctx.get_program_info_log(self.program).unwrap_or_default()
+    }
+
+    pub fn id(&self) -> ProgramId {
+        self.program
+    }
+
+    /// In strict mode unknown uniforms, values of wrong type and attributes missing from
+    /// drawn meshes are reported as errors instead of being silently skipped.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Attributes left active by the linker, unused declarations are optimized out.
+    pub fn attributes(&self) -> &[ActiveAttribute] {
+        &self.attributes
+    }
+
+    /// Uniforms left active by the linker, unused declarations are optimized out.
+    pub fn uniforms(&self) -> &[ActiveUniform] {
+        &self.uniforms
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&ActiveAttribute> {
+        self.attributes.iter().find(|a| a.name == name)
+    }
+
+    pub fn uniform(&self, name: &str) -> Option<&ActiveUniform> {
+        self.uniforms.iter().find(|u| u.name == name)
+    }
+
+    /// Checks value against reflected declaration, array elements (`lights[2]`) resolve to their array.
+    pub(crate) fn check_uniform(&self, name: &str, data: &UniformData) -> Result<(), Error> {
+        let base = name
+            .strip_suffix(']')
+            .and_then(|n| n.rsplit_once('['))
+            .filter(|(_, idx)| idx.chars().all(|c| c.is_ascii_digit()))
+            .map(|(base, _)| base);
+        let uniform = self.uniform(name)
+            .or_else(|| base.and_then(|b| self.uniform(b)))
+            .ok_or_else(|| Error::UnknownUniform(name.to_string()))?;
+
+        if uniform.gl_type != data.gl_type() {
+            return Err(Error::TypeMismatch {
+                name: name.to_string(),
+                expected: uniform.type_name(),
+                found: reflection::type_name(data.gl_type()),
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks every value against reflection the same way strict mode does.
+    pub fn validate<U: Uniforms>(&self, uniforms: &mut U) -> Result<(), Error> {
+        uniforms
+            .uniforms()
+            .iter()
+            .try_for_each(|(name, data)| self.check_uniform(name, data))
+    }
+
+    /// Runs `validateProgram` against current GL state (bound textures, enabled attributes),
+    /// which is slow and only meaningful right before a draw, so it is never done implicitly.
+    pub fn validate_state(&self) -> Result<(), Error> {
+        self.ctx.validate_program(self.program);
+        if self.ctx.get_program_parameter(self.program, GL::VALIDATE_STATUS) == 0 {
+            return Err(Error::ProgramValidate(self.info_log()));
+        }
+        Ok(())
+    }
+
+    /// Cached attribute location, `None` if program does not use the attribute.
+    pub fn attribute_location(&self, name: &str) -> Option<u32> {
+        if let Some(loc) = self.attribute_locations.borrow().get(name) {
+            return *loc;
+        }
+        let loc = Some(self.ctx.get_attrib_location(self.program, name))
+            .filter(|idx| *idx >= 0)
+            .map(|idx| idx as u32);
+        self.attribute_locations.borrow_mut().insert(name.to_string(), loc);
+        loc
+    }
+
+    /// Cached uniform location, `None` if program does not use the uniform.
+    pub fn uniform_location(&self, name: &str) -> Option<UniformLocation> {
+        if let Some(loc) = self.uniform_locations.borrow().get(name) {
+            return *loc;
+        }
+        let loc = self.ctx.get_uniform_location(self.program, name);
+        self.uniform_locations.borrow_mut().insert(name.to_string(), loc);
+        loc
+    }
+
+    fn shader(ctx: &Ctx, stage: ShaderStage, preamble: &str, source: &str) -> Result<ShaderId, Error> {
+        let shader = ctx.resource(ctx.create_shader(stage.into()), ResourceKind::Shader)?;
+        let (full_source, preamble_lines) = Program::prepend(preamble, source);
+        ctx.shader_source(shader, &full_source);
+        ctx.compile_shader(shader);
+
+        if ctx.get_shader_parameter(shader, GL::COMPILE_STATUS) != 0 {
             Ok(shader)
         } else {
-            Err(format!("Failed to compile shader {:?}", ctx.get_shader_info_log(&shader)))
+            let log = ctx.get_shader_info_log(shader).unwrap_or_default();
+            ctx.delete_shader(shader);
+            Err(Error::shader_compile(stage, log, source, preamble_lines))
+        }
+    }
+
+    /// Returns full source and range of lines occupied by preamble in it.
+    fn prepend(preamble: &str, source: &str) -> (String, Range<usize>) {
+        if preamble.is_empty() {
+            return (source.to_string(), 1..1);
+        }
+        let preamble = if preamble.ends_with('\n') { preamble.to_string() } else { format!("{}\n", preamble) };
+        let len = preamble.lines().count();
+
+        if source.trim_start().starts_with("#version") {
+            let version_end = source
+                .find("#version")
+                .and_then(|i| source[i..].find('\n').map(|j| i + j + 1))
+                .unwrap_or(source.len());
+            let (version, rest) = source.split_at(version_end);
+            let version = if version.ends_with('\n') { version.to_string() } else { format!("{}\n", version) };
+            let start = version.lines().count() + 1;
+            (format!("{}{}{}", version, preamble, rest), start..start + len)
+        } else {
+            (format!("{}{}", preamble, source), 1..1 + len)
         }
     }
 }
 
 impl Drop for Program {
     fn drop(&mut self) {
-        self.ctx.delete_program(Some(&self.program));
+        self.ctx.delete_program(self.program);
     }
 }
 
+/// Values are uploaded as is, see matrix variants for their memory layout.
 pub enum UniformData<'a> {
     Scalar(f32),
     Vector2([f32; 2]),
     Vector3([f32; 3]),
     Vector4([f32; 4]),
+    Int(i32),
+    IntVector2([i32; 2]),
+    IntVector3([i32; 3]),
+    IntVector4([i32; 4]),
+    Bool(bool),
+    BoolVector2([bool; 2]),
+    BoolVector3([bool; 3]),
+    BoolVector4([bool; 4]),
+    /// Column-major, columns stored one after another (`[m00, m10, m01, m11]`). WebGL 1 rejects
+    /// transposed uploads, so row-major data has to be transposed before it is passed here.
+    Matrix2([f32; 4]),
+    /// Column-major, same layout as `Matrix2`.
+    Matrix3([f32; 9]),
+    /// Column-major, same layout as `Matrix2`, translation is in elements 12, 13 and 14.
     Matrix4([f32; 16]),
     Texture(&'a mut UploadedTexture),
+    /// Arrays are uploaded starting at the element the name refers to, `lights` or `lights[2]`.
+    ScalarArray(&'a [f32]),
+    Vector2Array(&'a [[f32; 2]]),
+    Vector3Array(&'a [[f32; 3]]),
+    Vector4Array(&'a [[f32; 4]]),
+    IntArray(&'a [i32]),
+    IntVector2Array(&'a [[i32; 2]]),
+    IntVector3Array(&'a [[i32; 3]]),
+    IntVector4Array(&'a [[i32; 4]]),
+    BoolArray(&'a [bool]),
+    /// Each element column-major, same layout as `Matrix2`.
+    Matrix2Array(&'a [[f32; 4]]),
+    Matrix3Array(&'a [[f32; 9]]),
+    Matrix4Array(&'a [[f32; 16]]),
+    /// Textures are bound to consecutive texture units.
+    TextureArray(&'a [&'a UploadedTexture]),
+}
+
+/// Name of array element or struct member, e.g. `UniformName::new("lights").index(2).field("color")`
+/// gives `lights[2].color`, passed to `shade` as `name.as_ref()`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct UniformName(String);
+
+impl UniformName {
+    pub fn new(name: &str) -> Self {
+        Self(name.to_string())
+    }
+
+    pub fn field(mut self, field: &str) -> Self {
+        self.0.push('.');
+        self.0.push_str(field);
+        self
+    }
+
+    pub fn index(mut self, index: usize) -> Self {
+        self.0.push_str(&format!("[{}]", index));
+        self
+    }
+}
+
+impl AsRef<str> for UniformName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for UniformName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl UniformData<'_> {
+    /// GL type of the declaration this value can be assigned to.
+    pub fn gl_type(&self) -> u32 {
+        match self {
+            UniformData::Scalar(_) => GL::FLOAT,
+            UniformData::Vector2(_) => GL::FLOAT_VEC2,
+            UniformData::Vector3(_) => GL::FLOAT_VEC3,
+            UniformData::Vector4(_) => GL::FLOAT_VEC4,
+            UniformData::Int(_) => GL::INT,
+            UniformData::IntVector2(_) => GL::INT_VEC2,
+            UniformData::IntVector3(_) => GL::INT_VEC3,
+            UniformData::IntVector4(_) => GL::INT_VEC4,
+            UniformData::Bool(_) => GL::BOOL,
+            UniformData::BoolVector2(_) => GL::BOOL_VEC2,
+            UniformData::BoolVector3(_) => GL::BOOL_VEC3,
+            UniformData::BoolVector4(_) => GL::BOOL_VEC4,
+            UniformData::Matrix2(_) => GL::FLOAT_MAT2,
+            UniformData::Matrix3(_) => GL::FLOAT_MAT3,
+            UniformData::Matrix4(_) => GL::FLOAT_MAT4,
+            UniformData::Texture(_) => GL::SAMPLER_2D,
+            UniformData::ScalarArray(_) => GL::FLOAT,
+            UniformData::Vector2Array(_) => GL::FLOAT_VEC2,
+            UniformData::Vector3Array(_) => GL::FLOAT_VEC3,
+            UniformData::Vector4Array(_) => GL::FLOAT_VEC4,
+            UniformData::IntArray(_) => GL::INT,
+            UniformData::IntVector2Array(_) => GL::INT_VEC2,
+            UniformData::IntVector3Array(_) => GL::INT_VEC3,
+            UniformData::IntVector4Array(_) => GL::INT_VEC4,
+            UniformData::BoolArray(_) => GL::BOOL,
+            UniformData::Matrix2Array(_) => GL::FLOAT_MAT2,
+            UniformData::Matrix3Array(_) => GL::FLOAT_MAT3,
+            UniformData::Matrix4Array(_) => GL::FLOAT_MAT4,
+            UniformData::TextureArray(_) => GL::SAMPLER_2D,
+        }
+    }
 }
 
 pub struct Pipeline {
@@ -98,89 +425,483 @@ pub struct Pipeline {
     clear_color: Option<[f32; 4]>,
     clear_depth: Option<f32>,
     clear_stencil: Option<i32>,
+    render_state: RenderState,
+    strict: bool,
 }
 
 impl Pipeline {
+    /// Clears color to opaque black, depth to `1.0` and stencil to `0` on every `shade`.
     pub fn new(ctx: &Ctx) -> Self {
-        let s = Self {
+        Self {
             ctx: ctx.clone(),
             clear_color: Some([0., 0., 0., 1.]),
             clear_depth: Some(1.),
             clear_stencil: Some(0),
-        };
-
-        if let Some(col) = s.clear_color {
-            ctx.clear_color(col[0], col[1], col[2], col[3]);
-        }
-        if let Some(d) = s.clear_depth {
-            ctx.clear_depth(d);
+            render_state: RenderState::default(),
+            strict: false,
         }
-        if let Some(s) = s.clear_stencil {
-            ctx.clear_stencil(s);
-        };
+    }
+
+    /// `None` keeps color buffer contents.
+    pub fn with_clear_color(mut self, color: Option<[f32; 4]>) -> Self {
+        self.clear_color = color;
+        self
+    }
+
+    /// `None` keeps depth buffer contents.
+    pub fn with_clear_depth(mut self, depth: Option<f32>) -> Self {
+        self.clear_depth = depth;
+        self
+    }
+
+    /// `None` keeps stencil buffer contents.
+    pub fn with_clear_stencil(mut self, stencil: Option<i32>) -> Self {
+        self.clear_stencil = stencil;
+        self
+    }
+
+    /// Validates every program shaded by this pipeline as if it was created with `Program::with_strict`.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
 
-        s
+    /// Fixed function state set before every `shade`.
+    pub fn with_render_state(mut self, state: RenderState) -> Self {
+        self.render_state = state;
+        self
     }
 
+    /// Replaces render state for following `shade` calls, e.g. to draw transparent objects after opaque ones.
+    pub fn set_render_state(&mut self, state: RenderState) {
+        self.render_state = state;
+    }
+
+    pub fn render_state(&self) -> &RenderState {
+        &self.render_state
+    }
+
+    /// Clears output with configured clear values and draws objects.
     pub fn shade<'a, T, U>(
         &mut self,
         program: &Program,
         uni_values: U,
         objects: Vec<&mut Mesh>,
         output: &'a mut T,
-    ) -> Result<&Self, String> where
+    ) -> Result<&Self, Error> where
         T: Framebuffer,
-        U: IntoIterator<Item = (&'a str, UniformData<'a>)>
+        U: IntoIterator<Item = (&'a str, UniformData<'a>)>,
     {
-        output.bind();
+        self.begin(output, true)?;
+        self.draw(program, uni_values, objects)
+    }
 
-        if self.clear_color.is_some() {
-            self.ctx.clear(GL::COLOR_BUFFER_BIT);
-        }
-        if self.clear_depth.is_some() {
-            self.ctx.clear(GL::DEPTH_BUFFER_BIT);
-        }
-        if self.clear_stencil.is_some() {
-            self.ctx.clear(GL::STENCIL_BUFFER_BIT);
-        }
+    /// Same as `shade` but keeps output contents, for drawing several programs into the same target.
+    pub fn shade_without_clear<'a, T, U>(
+        &mut self,
+        program: &Program,
+        uni_values: U,
+        objects: Vec<&mut Mesh>,
+        output: &'a mut T,
+    ) -> Result<&Self, Error> where
+        T: Framebuffer,
+        U: IntoIterator<Item = (&'a str, UniformData<'a>)>,
+    {
+        self.begin(output, false)?;
+        self.draw(program, uni_values, objects)
+    }
 
-        self.ctx.use_program(Some(&program.program));
+    fn draw<'a, U>(
+        &mut self,
+        program: &Program,
+        uni_values: U,
+        objects: Vec<&mut Mesh>,
+    ) -> Result<&Self, Error> where
+        U: IntoIterator<Item = (&'a str, UniformData<'a>)>,
+    {
+        self.ctx.use_program(Some(program.program));
         self.set_uniforms(program, uni_values)?;
 
         for obj in objects {
-            obj.draw(program)?;
+            obj.draw_checked(program, self.strict || program.strict, None)?;
+        }
+
+        Ok(self)
+    }
+
+    /// Draws submeshes of a single mesh, each with its own program and uniforms, output is cleared once.
+    ///
+    /// ```ignore
+    /// let model = Mesh::from_vertices(&ctx, &vertices, &indices)?
+    ///     .with_submesh(Submesh::new(0, 300))
+    ///     .with_submesh(Submesh::new(300, 120));
+    ///
+    /// pipeline.shade_submeshes(&mut model, vec![
+    ///     (&metal, metal_uniforms, 0),
+    ///     (&glass, glass_uniforms, 1),
+    /// ], &mut output)?;
+    /// ```
+    pub fn shade_submeshes<'a, T, U>(
+        &mut self,
+        mesh: &mut Mesh,
+        passes: Vec<(&Program, U, usize)>,
+        output: &'a mut T,
+    ) -> Result<&Self, Error> where
+        T: Framebuffer,
+        U: IntoIterator<Item = (&'a str, UniformData<'a>)>,
+    {
+        self.begin(output, true)?;
+        self.draw_submeshes(mesh, passes)
+    }
+
+    /// Same as `shade_submeshes` but keeps output contents.
+    pub fn shade_submeshes_without_clear<'a, T, U>(
+        &mut self,
+        mesh: &mut Mesh,
+        passes: Vec<(&Program, U, usize)>,
+        output: &'a mut T,
+    ) -> Result<&Self, Error> where
+        T: Framebuffer,
+        U: IntoIterator<Item = (&'a str, UniformData<'a>)>,
+    {
+        self.begin(output, false)?;
+        self.draw_submeshes(mesh, passes)
+    }
+
+    fn draw_submeshes<'a, U>(
+        &mut self,
+        mesh: &mut Mesh,
+        passes: Vec<(&Program, U, usize)>,
+    ) -> Result<&Self, Error> where
+        U: IntoIterator<Item = (&'a str, UniformData<'a>)>,
+    {
+        for (program, uni_values, submesh) in passes {
+            self.ctx.use_program(Some(program.program));
+            self.set_uniforms(program, uni_values)?;
+            mesh.draw_checked(program, self.strict || program.strict, Some(submesh))?;
         }
 
         Ok(self)
     }
 
+    fn begin<T: Framebuffer>(&self, output: &mut T, clear: bool) -> Result<(), Error> {
+        if self.ctx.is_context_lost() {
+            return Err(Error::ContextLost);
+        }
+        output.bind();
+        if clear {
+            self.clear();
+        }
+        self.render_state.apply(&self.ctx);
+
+        Ok(())
+    }
+
+    /// Clear values are context wide, so they are set on every pass in case another pipeline changed them.
+    fn clear(&self) {
+        let mut mask = 0;
+        if let Some([r, g, b, a]) = self.clear_color {
+            self.ctx.clear_color(r, g, b, a);
+            mask |= GL::COLOR_BUFFER_BIT;
+        }
+        if let Some(depth) = self.clear_depth {
+            self.ctx.clear_depth(depth);
+            mask |= GL::DEPTH_BUFFER_BIT;
+        }
+        if let Some(stencil) = self.clear_stencil {
+            self.ctx.clear_stencil(stencil);
+            mask |= GL::STENCIL_BUFFER_BIT;
+        }
+        if mask != 0 {
+            // masks and scissor would limit clears, buffers are cleared as a whole
+            self.render_state.for_clear().apply(&self.ctx);
+            self.ctx.clear(mask);
+        }
+    }
+
     fn set_uniforms<'a, U>(
         &self,
         program: &Program,
         uniform_values: U,
-    ) -> Result<&Self, String> where
-        U: IntoIterator<Item = (&'a str, UniformData<'a>)>
+    ) -> Result<&Self, Error> where
+        U: IntoIterator<Item = (&'a str, UniformData<'a>)>,
     {
+        let strict = self.strict || program.strict;
         let mut tex_inc = 0;
         for (name, uni_val) in uniform_values {
-            if let Some(loc) = self.ctx.get_uniform_location(&program.program, name) {
+            if strict {
+                program.check_uniform(name, &uni_val)?;
+            }
+            if let Some(loc) = program.uniform_location(name) {
                 match uni_val {
-                    UniformData::Scalar(v) => self.ctx.uniform1f(Some(&loc), v),
-                    UniformData::Vector2(v) => self.ctx.uniform2fv_with_f32_array(Some(&loc), &v),
-                    UniformData::Vector3(v) => self.ctx.uniform3fv_with_f32_array(Some(&loc), &v),
-                    UniformData::Vector4(v) => self.ctx.uniform4fv_with_f32_array(Some(&loc), &v),
-                    UniformData::Matrix4(m) => self.ctx.uniform_matrix4fv_with_f32_array(Some(&loc), false, &m),
+                    UniformData::Scalar(v) => self.ctx.uniform1f(loc, v),
+                    UniformData::Vector2(v) => self.ctx.uniform2fv(loc, &v),
+                    UniformData::Vector3(v) => self.ctx.uniform3fv(loc, &v),
+                    UniformData::Vector4(v) => self.ctx.uniform4fv(loc, &v),
+                    UniformData::Int(v) => self.ctx.uniform1i(loc, v),
+                    UniformData::IntVector2(v) => self.ctx.uniform2iv(loc, &v),
+                    UniformData::IntVector3(v) => self.ctx.uniform3iv(loc, &v),
+                    UniformData::IntVector4(v) => self.ctx.uniform4iv(loc, &v),
+                    // booleans are set through integer entry points
+                    UniformData::Bool(v) => self.ctx.uniform1i(loc, v as i32),
+                    UniformData::BoolVector2(v) => self.ctx.uniform2iv(loc, &v.map(i32::from)),
+                    UniformData::BoolVector3(v) => self.ctx.uniform3iv(loc, &v.map(i32::from)),
+                    UniformData::BoolVector4(v) => self.ctx.uniform4iv(loc, &v.map(i32::from)),
+                    UniformData::Matrix2(m) => self.ctx.uniform_matrix2fv(loc, false, &m),
+                    UniformData::Matrix3(m) => self.ctx.uniform_matrix3fv(loc, false, &m),
+                    UniformData::Matrix4(m) => self.ctx.uniform_matrix4fv(loc, false, &m),
                     UniformData::Texture(tex) => {
                         self.ctx.active_texture(GL::TEXTURE0 + tex_inc);
                         tex.bind();
 
                         // todo: double check on safely disposing uniforms data
-                        self.ctx.uniform1i(Some(&loc), tex_inc as i32);
+                        self.ctx.uniform1i(loc, tex_inc as i32);
                         tex_inc += 1;
                     }
+                    UniformData::ScalarArray(v) => self.ctx.uniform1fv(loc, v),
+                    UniformData::Vector2Array(v) => self.ctx.uniform2fv(loc, v.as_flattened()),
+                    UniformData::Vector3Array(v) => self.ctx.uniform3fv(loc, v.as_flattened()),
+                    UniformData::Vector4Array(v) => self.ctx.uniform4fv(loc, v.as_flattened()),
+                    UniformData::IntArray(v) => self.ctx.uniform1iv(loc, v),
+                    UniformData::IntVector2Array(v) => self.ctx.uniform2iv(loc, v.as_flattened()),
+                    UniformData::IntVector3Array(v) => self.ctx.uniform3iv(loc, v.as_flattened()),
+                    UniformData::IntVector4Array(v) => self.ctx.uniform4iv(loc, v.as_flattened()),
+                    UniformData::BoolArray(v) => {
+                        let v = v.iter().map(|&b| b as i32).collect::<Vec<_>>();
+                        self.ctx.uniform1iv(loc, &v);
+                    }
+                    UniformData::Matrix2Array(m) => self.ctx.uniform_matrix2fv(loc, false, m.as_flattened()),
+                    UniformData::Matrix3Array(m) => self.ctx.uniform_matrix3fv(loc, false, m.as_flattened()),
+                    UniformData::Matrix4Array(m) => self.ctx.uniform_matrix4fv(loc, false, m.as_flattened()),
+                    UniformData::TextureArray(textures) => {
+                        let mut units = vec![];
+                        for tex in textures {
+                            self.ctx.active_texture(GL::TEXTURE0 + tex_inc);
+                            tex.bind();
+                            units.push(tex_inc as i32);
+                            tex_inc += 1;
+                        }
+                        self.ctx.uniform1iv(loc, &units);
+                    }
                 }
             }
         }
         Ok(self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attributes::AttributeVector2;
+    use crate::backend::{MockBackend, UniformValue};
+    use crate::backend::mock::Call;
+
+    const VERTEX: &str = "attribute vec2 position; uniform mat4 u_transform; void main() {}";
+    const FRAGMENT: &str = "uniform vec4 u_color; uniform sampler2D u_albedo; uniform sampler2D u_normal; void main() {}";
+
+    fn setup() -> (MockBackend, Ctx) {
+        let mock = MockBackend::new();
+        let ctx = Ctx::with_backend(mock.clone()).unwrap();
+        (mock, ctx)
+    }
+
+    fn triangle(ctx: &Ctx) -> Mesh {
+        Mesh::new(ctx, &[0, 1, 2]).unwrap()
+            .with_attribute::<AttributeVector2>("position", &[[0., 0.], [1., 0.], [0., 1.]]).unwrap()
+    }
+
+    struct Material {
+        color: [f32; 4],
+    }
+
+    impl Uniforms for Material {
+        fn uniforms(&mut self) -> Vec<(&'static str, UniformData<'_>)> {
+            vec![("u_color", UniformData::Vector4(self.color))]
+        }
+    }
+
+    #[test]
+    fn program_links_and_reflects_declarations() {
+        let (mock, ctx) = setup();
+        let program = Program::new(&ctx, VERTEX, FRAGMENT).unwrap();
+
+        assert!(program.attribute("position").is_some());
+        assert_eq!(program.uniform("u_color").map(|u| u.gl_type), Some(GL::FLOAT_VEC4));
+        assert_eq!(mock.live_shaders(), 0);
+        assert!(!mock.calls().contains(&Call::ValidateProgram(program.id())));
+        assert_eq!(program.validate_state(), Ok(()));
+
+        drop(program);
+        assert_eq!(mock.live_programs(), 0);
+    }
+
+    #[test]
+    fn compile_failure_reports_stage_and_log() {
+        let (mock, ctx) = setup();
+        let log = "ERROR: 0:1: 'u_transform' : undeclared identifier";
+        mock.fail_next_compile(log);
+
+        match Program::new(&ctx, VERTEX, FRAGMENT) {
+            Err(Error::ShaderCompile { stage, log: reported, source_line, .. }) => {
+                assert_eq!(stage, ShaderStage::Vertex);
+                assert_eq!(reported, log);
+                assert_eq!(source_line.as_deref(), Some(VERTEX));
+            }
+            other => panic!("expected compile error, got {:?}", other.err()),
+        }
+        assert_eq!(mock.live_shaders(), 0);
+        assert_eq!(mock.live_programs(), 0);
+    }
+
+    #[test]
+    fn link_failure_reports_log_and_releases_resources() {
+        let (mock, ctx) = setup();
+        mock.fail_next_link("varying v_uv is not written");
+
+        let result = Program::new(&ctx, VERTEX, FRAGMENT).err();
+
+        assert_eq!(result, Some(Error::ProgramLink("varying v_uv is not written".to_string())));
+        assert_eq!(mock.live_shaders(), 0);
+        assert_eq!(mock.live_programs(), 0);
+    }
+
+    #[test]
+    fn validate_checks_uniforms_against_reflection() {
+        let (_, ctx) = setup();
+        let program = Program::new(&ctx, VERTEX, FRAGMENT).unwrap();
+
+        assert_eq!(program.validate(&mut Material { color: [1.; 4] }), Ok(()));
+
+        struct Wrong;
+        impl Uniforms for Wrong {
+            fn uniforms(&mut self) -> Vec<(&'static str, UniformData<'_>)> {
+                vec![("u_color", UniformData::Vector3([1.; 3]))]
+            }
+        }
+        assert_eq!(
+            program.validate(&mut Wrong),
+            Err(Error::TypeMismatch { name: "u_color".to_string(), expected: "vec4", found: "vec3" }),
+        );
+    }
+
+    #[test]
+    fn uniforms_are_uploaded_to_their_locations() {
+        let (mock, ctx) = setup();
+        let program = Program::new(&ctx, VERTEX, FRAGMENT).unwrap();
+        let mut mesh = triangle(&ctx);
+        let mut output = EmptyFramebuffer::new(&ctx, Viewport::new(4, 4));
+        let mut transform = [0.; 16];
+        transform[0] = 2.;
+
+        Pipeline::new(&ctx).shade(
+            &program,
+            vec![
+                ("u_color", UniformData::Vector4([1., 0., 0., 1.])),
+                ("u_transform", UniformData::Matrix4(transform)),
+                ("u_unused", UniformData::Scalar(1.)),
+            ],
+            vec![&mut mesh],
+            &mut output,
+        ).unwrap();
+
+        assert_eq!(mock.current_program(), Some(program.id()));
+        assert_eq!(mock.uniform(program.id(), "u_color"), Some(UniformValue::FloatVec4(vec![1., 0., 0., 1.])));
+        assert_eq!(
+            mock.uniform(program.id(), "u_transform"),
+            Some(UniformValue::FloatMat4 { transpose: false, data: transform.to_vec() }),
+        );
+    }
+
+    #[test]
+    fn int_and_bool_arrays_are_uploaded_flattened() {
+        let (mock, ctx) = setup();
+        let fragment = "uniform ivec2 u_cells[2]; uniform bool u_lit[3]; void main() {}";
+        let program = Program::new(&ctx, VERTEX, fragment).unwrap().with_strict(true);
+        let mut mesh = triangle(&ctx);
+        let mut output = EmptyFramebuffer::new(&ctx, Viewport::new(4, 4));
+
+        Pipeline::new(&ctx).shade(
+            &program,
+            vec![
+                ("u_cells", UniformData::IntVector2Array(&[[1, 2], [3, 4]])),
+                ("u_lit", UniformData::BoolArray(&[true, false, true])),
+            ],
+            vec![&mut mesh],
+            &mut output,
+        ).unwrap();
+
+        assert_eq!(mock.uniform(program.id(), "u_cells"), Some(UniformValue::IntVec2(vec![1, 2, 3, 4])));
+        assert_eq!(mock.uniform(program.id(), "u_lit"), Some(UniformValue::IntArray(vec![1, 0, 1])));
+    }
+
+    #[test]
+    fn shade_accepts_empty_uniform_list() {
+        let (_, ctx) = setup();
+        let program = Program::new(&ctx, VERTEX, FRAGMENT).unwrap();
+        let mut mesh = triangle(&ctx);
+        let mut output = EmptyFramebuffer::new(&ctx, Viewport::new(4, 4));
+
+        Pipeline::new(&ctx).shade(&program, vec![], vec![&mut mesh], &mut output).unwrap();
+    }
+
+    #[test]
+    fn strict_pipeline_rejects_unknown_uniforms() {
+        let (_, ctx) = setup();
+        let program = Program::new(&ctx, VERTEX, FRAGMENT).unwrap();
+        let mut mesh = triangle(&ctx);
+        let mut output = EmptyFramebuffer::new(&ctx, Viewport::new(4, 4));
+
+        let result = Pipeline::new(&ctx).with_strict(true).shade(
+            &program,
+            vec![("u_unused", UniformData::Scalar(1.))],
+            vec![&mut mesh],
+            &mut output,
+        ).err();
+
+        assert_eq!(result, Some(Error::UnknownUniform("u_unused".to_string())));
+    }
+
+    #[test]
+    fn submeshes_are_cleared_only_when_asked() {
+        let (mock, ctx) = setup();
+        let program = Program::new(&ctx, VERTEX, FRAGMENT).unwrap();
+        let mut mesh = triangle(&ctx).with_submesh(mesh::Submesh::new(0, 3));
+        let mut output = EmptyFramebuffer::new(&ctx, Viewport::new(4, 4));
+        let mut pipeline = Pipeline::new(&ctx).with_clear_color(Some([0., 0., 0., 1.]));
+        let color = || vec![("u_color", UniformData::Vector4([1.; 4]))];
+        mock.take_calls();
+
+        pipeline.shade_submeshes(&mut mesh, vec![(&program, color(), 0)], &mut output).unwrap();
+        assert!(mock.take_calls().iter().any(|c| matches!(c, Call::Clear(_))));
+
+        pipeline.shade_submeshes_without_clear(&mut mesh, vec![(&program, color(), 0)], &mut output).unwrap();
+        let calls = mock.take_calls();
+        assert!(!calls.iter().any(|c| matches!(c, Call::Clear(_))));
+        assert!(calls.iter().any(|c| matches!(c, Call::DrawElements { count: 3, .. })));
+    }
+
+    #[test]
+    fn textures_are_bound_to_consecutive_units() {
+        let (mock, ctx) = setup();
+        let program = Program::new(&ctx, VERTEX, FRAGMENT).unwrap();
+        let mut mesh = triangle(&ctx);
+        let mut output = EmptyFramebuffer::new(&ctx, Viewport::new(4, 4));
+        let spec = TextureSpec::new(ColorFormat(GL::RGBA), [1, 1]);
+        let mut albedo = spec.upload_u8(&ctx, &[255; 4]).unwrap();
+        let mut normal = spec.upload_u8(&ctx, &[128; 4]).unwrap();
+        let (albedo_id, normal_id) = (albedo.id(), normal.id());
+
+        Pipeline::new(&ctx).shade(
+            &program,
+            vec![("u_albedo", UniformData::Texture(&mut albedo)), ("u_normal", UniformData::Texture(&mut normal))],
+            vec![&mut mesh],
+            &mut output,
+        ).unwrap();
+
+        assert_eq!(mock.texture_unit(0), Some(albedo_id));
+        assert_eq!(mock.texture_unit(1), Some(normal_id));
+        assert_eq!(mock.uniform(program.id(), "u_albedo"), Some(UniformValue::Int(0)));
+        assert_eq!(mock.uniform(program.id(), "u_normal"), Some(UniformValue::Int(1)));
+    }
+}
@@ -1,135 +1,1146 @@
+use std::cell::Cell;
 use std::collections::HashMap;
+use std::rc::{Rc, Weak};
 
-use web_sys::WebGlBuffer;
+use crate::{GL, Ctx, Error, Program};
+use crate::backend::{BufferId, ProgramId, VertexArrayId};
+use crate::error::ResourceKind;
+use crate::reflection::type_name;
+use crate::attributes::{vertex_bytes, Attribute, Vertex, VertexAttribute};
 
-use crate::{GL, Ctx, Program};
-use crate::attributes::{Attribute, AttributeType};
+/// Hint on how often buffer contents are going to change.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BufferUsage {
+    /// Uploaded once, drawn many times.
+    Static,
+    /// Updated now and then, drawn many times.
+    Dynamic,
+    /// Updated before every draw.
+    Stream,
+}
 
-struct VertexBuffer {
+impl From<BufferUsage> for u32 {
+    fn from(usage: BufferUsage) -> Self {
+        match usage {
+            BufferUsage::Static => GL::STATIC_DRAW,
+            BufferUsage::Dynamic => GL::DYNAMIC_DRAW,
+            BufferUsage::Stream => GL::STREAM_DRAW,
+        }
+    }
+}
+
+struct Buffer {
     ctx: Ctx,
-    att: AttributeType,
-    buffer: WebGlBuffer,
+    buffer: BufferId,
+    target: u32,
+    usage: BufferUsage,
+    /// Bytes allocated by last `buffer_data`.
+    capacity: Cell<usize>,
+    /// Bytes holding current contents, vertex and index counts are derived from it.
+    size: Cell<usize>,
 }
 
-impl VertexBuffer {
-    fn new<T: Attribute>(ctx: &Ctx, att: AttributeType, data: &T::Repr) -> Result<Self, String> {
-        let buffer = ctx
-            .create_buffer()
-            .ok_or("Failed to create element buffer")?;
-        ctx.bind_buffer(GL::ARRAY_BUFFER, Some(&buffer));
-        ctx.buffer_data_with_u8_array(GL::ARRAY_BUFFER, &T::pack(data), GL::STATIC_DRAW);
+impl Buffer {
+    fn new(ctx: &Ctx, target: u32, data: &[u8], usage: BufferUsage) -> Result<Self, Error> {
+        let buffer = ctx.resource(ctx.create_buffer(), ResourceKind::Buffer)?;
+        ctx.bind_buffer(target, Some(buffer));
+        ctx.buffer_data(target, data, usage.into());
 
         Ok(Self {
             ctx: ctx.clone(),
-            att,
             buffer,
+            target,
+            usage,
+            capacity: Cell::new(data.len()),
+            size: Cell::new(data.len()),
         })
     }
 
-    fn bind(&mut self, ptr_idx: u32) {
-        self.ctx.bind_buffer(GL::ARRAY_BUFFER, Some(&self.buffer));
-        self.ctx.vertex_attrib_pointer_with_i32(
-            ptr_idx,
-            self.att.num_components(),
-            GL::FLOAT,
-            false,
-            0,
-            0,
-        );
+    fn bind(&self) {
+        self.ctx.bind_buffer(self.target, Some(self.buffer));
+    }
+
+    /// Replaces buffer contents with `data`. Data filling the whole storage (or any data for streamed
+    /// buffers) reallocates it instead, so the driver can orphan the old one rather than wait for draws
+    /// still using it, this is also how buffer grows. Smaller data is written into existing storage.
+    fn replace(&self, data: &[u8]) {
+        self.bind();
+        if data.len() >= self.capacity.get() || self.usage == BufferUsage::Stream {
+            self.ctx.buffer_data(self.target, data, self.usage.into());
+            self.capacity.set(data.len());
+        } else {
+            self.ctx.buffer_sub_data(self.target, 0, data);
+        }
+        self.size.set(data.len());
+    }
+
+    /// Writes `data` at byte `offset` within current contents. Ranged writes never grow the buffer,
+    /// WebGL 1 can not copy the preserved part into new storage, use `replace` to resize.
+    fn write(&self, offset: usize, data: &[u8]) -> Result<(), Error> {
+        if offset + data.len() > self.size.get() {
+            return Err(Error::BufferUpdate(format!(
+                "{} bytes at offset {} do not fit into {} bytes",
+                data.len(), offset, self.size.get(),
+            )));
+        }
+        if offset == 0 && data.len() == self.capacity.get() {
+            self.replace(data);
+        } else {
+            self.bind();
+            self.ctx.buffer_sub_data(self.target, offset as i32, data);
+        }
+        Ok(())
     }
 }
 
-impl Drop for VertexBuffer {
+impl Drop for Buffer {
     fn drop(&mut self) {
-        self.ctx.delete_buffer(Some(&self.buffer));
+        self.ctx.delete_buffer(self.buffer);
+    }
+}
+
+/// Array buffer, shared by all attributes of interleaved vertices.
+struct VertexBuffer {
+    ctx: Ctx,
+    buffer: Buffer,
+    stride: i32,
+    /// Non-zero for per-instance data, advancing once per `divisor` instances.
+    divisor: u32,
+}
+
+impl VertexBuffer {
+    fn new(ctx: &Ctx, data: &[u8], stride: i32, usage: BufferUsage) -> Result<Self, Error> {
+        Ok(Self {
+            ctx: ctx.clone(),
+            buffer: Buffer::new(ctx, GL::ARRAY_BUFFER, data, usage)?,
+            stride,
+            divisor: 0,
+        })
+    }
+
+    fn per_instance(mut self, divisor: u32) -> Self {
+        self.divisor = divisor;
+        self
+    }
+
+    fn instance_count(&self, att: &VertexAttribute) -> usize {
+        self.vertex_count(att) * self.divisor as usize
+    }
+
+    fn vertex_size(&self, att: &VertexAttribute) -> usize {
+        if self.stride == 0 {
+            att.column_size() * att.columns as usize
+        } else {
+            self.stride as usize
+        }
+    }
+
+    fn vertex_count(&self, att: &VertexAttribute) -> usize {
+        self.buffer.size.get() / self.vertex_size(att).max(1)
+    }
+
+    /// Binds attribute so that vertex `base_vertex` is the first one, per-instance data always starts at first instance.
+    fn bind(&self, ptr_idx: u32, att: &VertexAttribute, base_vertex: usize) {
+        self.buffer.bind();
+        let vertex_size = self.vertex_size(att);
+        let base_vertex = if self.divisor == 0 { base_vertex } else { 0 };
+        // tightly packed matrix columns still need explicit stride
+        let stride = if self.stride == 0 && att.columns > 1 { vertex_size as i32 } else { self.stride };
+        for column in 0..att.columns {
+            self.ctx.vertex_attrib_pointer(
+                ptr_idx + column as u32,
+                att.size,
+                att.data_type,
+                att.normalized,
+                stride,
+                (base_vertex * vertex_size + att.offset + column as usize * att.column_size()) as i32,
+            );
+            if self.divisor != 0 {
+                self.ctx.vertex_attrib_divisor(ptr_idx + column as u32, self.divisor);
+            }
+        }
     }
 }
 
 struct ElementBuffer {
     ctx: Ctx,
-    buffer: WebGlBuffer,
+    buffer: Buffer,
+    data_type: u32,
+    element_size_bytes: usize,
     num_elements: usize,
+    /// Vertex index `0` refers to, attributes are bound with matching offset.
+    base_vertex: usize,
 }
 
 impl ElementBuffer {
-    fn new(ctx: &Ctx, element_size_bytes: usize, data: &[u8]) -> Result<Self, String> {
-        let buffer = ctx
-            .create_buffer()
-            .ok_or("Failed to create element buffer")?;
-        ctx.bind_buffer(GL::ELEMENT_ARRAY_BUFFER, Some(&buffer));
-        ctx.buffer_data_with_u8_array(GL::ELEMENT_ARRAY_BUFFER, data, GL::STATIC_DRAW);
-        let num_elements = data.len() / element_size_bytes;
+    fn new<I: Index>(ctx: &Ctx, indices: &[I], base_vertex: usize) -> Result<Self, Error> {
+        let buffer = Buffer::new(ctx, GL::ELEMENT_ARRAY_BUFFER, &index_bytes(indices), BufferUsage::Static)?;
 
         Ok(Self {
             ctx: ctx.clone(),
-            num_elements,
             buffer,
+            data_type: I::DATA_TYPE,
+            element_size_bytes: std::mem::size_of::<I>(),
+            num_elements: indices.len(),
+            base_vertex,
         })
     }
 
-    fn draw(&self, mode: MeshMode) {
-        self.ctx.bind_buffer(GL::ELEMENT_ARRAY_BUFFER, Some(&self.buffer));
-        self.ctx.draw_elements_with_i32(
-            mode.0,
-            self.num_elements as i32,
-            GL::UNSIGNED_SHORT,
-        0);
+    fn update(&mut self, data: &[u8]) {
+        self.buffer.replace(data);
+        self.num_elements = data.len() / self.element_size_bytes;
     }
+
+    fn draw(&self, mode: MeshMode, first: usize, count: usize, instances: Option<usize>) {
+        let offset = (first * self.element_size_bytes) as i32;
+        match instances {
+            Some(n) => self.ctx.draw_elements_instanced(mode.0, count as i32, self.data_type, offset, n as i32),
+            None => self.ctx.draw_elements(mode.0, count as i32, self.data_type, offset),
+        }
+    }
+}
+
+/// Element index type, `u32` needs `OES_element_index_uint`.
+pub trait Index: Copy + Into<u32> {
+    const DATA_TYPE: u32;
+
+    fn write_ne_bytes(self, bytes: &mut Vec<u8>);
+}
+
+impl Index for u8 {
+    const DATA_TYPE: u32 = GL::UNSIGNED_BYTE;
+
+    fn write_ne_bytes(self, bytes: &mut Vec<u8>) {
+        bytes.push(self)
+    }
+}
+
+impl Index for u16 {
+    const DATA_TYPE: u32 = GL::UNSIGNED_SHORT;
+
+    fn write_ne_bytes(self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.to_ne_bytes())
+    }
+}
+
+impl Index for u32 {
+    const DATA_TYPE: u32 = GL::UNSIGNED_INT;
+
+    fn write_ne_bytes(self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.to_ne_bytes())
+    }
+}
+
+fn index_bytes<I: Index>(indices: &[I]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(std::mem::size_of_val(indices));
+    for i in indices {
+        i.write_ne_bytes(&mut bytes);
+    }
+    bytes
+}
+
+/// Splits indices into `u16` chunks for contexts without `OES_element_index_uint`. WebGL 1 has no
+/// base vertex, so each chunk is drawn with attributes offset to its smallest index instead.
+/// Indices are taken in groups of 6, so points, lines and triangles are never cut in half, group
+/// joins current chunk while smallest and largest index of both stay within `u16` range, in any order.
+/// Strips, loops and fans can not be split.
+fn split_indices(indices: &[u32]) -> Result<Vec<(usize, Vec<u16>)>, Error> {
+    let mut chunks: Vec<(u32, u32, Vec<u32>)> = vec![];
+    for group in indices.chunks(6) {
+        let min = *group.iter().min().unwrap_or(&0);
+        let max = *group.iter().max().unwrap_or(&0);
+        match chunks.last_mut() {
+            Some((lo, hi, chunk)) if max.max(*hi) - min.min(*lo) <= u16::MAX as u32 => {
+                *lo = min.min(*lo);
+                *hi = max.max(*hi);
+                chunk.extend_from_slice(group);
+            }
+            _ if max - min <= u16::MAX as u32 => chunks.push((min, max, group.to_vec())),
+            _ => return Err(Error::MissingExtension("OES_element_index_uint")),
+        }
+    }
+    Ok(chunks
+        .into_iter()
+        .map(|(base, _, chunk)| (base as usize, chunk.iter().map(|i| (i - base) as u16).collect()))
+        .collect())
 }
 
-impl Drop for ElementBuffer {
+fn element_buffers<I: Index>(ctx: &Ctx, indices: &[I], split: bool) -> Result<Vec<ElementBuffer>, Error> {
+    if I::DATA_TYPE != GL::UNSIGNED_INT {
+        return Ok(vec![ElementBuffer::new(ctx, indices, 0)?]);
+    }
+    match ctx.require_extension("OES_element_index_uint") {
+        Ok(()) => Ok(vec![ElementBuffer::new(ctx, indices, 0)?]),
+        Err(_) if split => {
+            let indices = indices.iter().map(|i| (*i).into()).collect::<Vec<u32>>();
+            split_indices(&indices)?
+                .into_iter()
+                .map(|(base, chunk)| ElementBuffer::new(ctx, &chunk, base))
+                .collect()
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Attribute location along with buffer and layout bound to it.
+type Binding<'a> = (u32, &'a VertexBuffer, &'a VertexAttribute);
+
+/// Attribute bindings of a mesh recorded for one program.
+struct VertexArray {
+    ctx: Ctx,
+    id: VertexArrayId,
+}
+
+impl VertexArray {
+    fn new(ctx: &Ctx) -> Result<Self, Error> {
+        let id = ctx.resource(ctx.create_vertex_array(), ResourceKind::VertexArray)?;
+        Ok(Self { ctx: ctx.clone(), id })
+    }
+}
+
+impl Drop for VertexArray {
     fn drop(&mut self) {
-        self.ctx.delete_buffer(Some(&self.buffer));
+        self.ctx.delete_vertex_array(self.id);
     }
 }
 
-#[derive(Clone, Copy)]
+/// Vertex arrays of a mesh recorded for one program, one per element buffer.
+struct RecordedArrays {
+    /// Dead once program is dropped, its id may then be handed out again.
+    program: Weak<()>,
+    arrays: Vec<VertexArray>,
+}
+
+/// Primitive type meshes are drawn with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct MeshMode(u32);
 
+impl MeshMode {
+    pub fn points() -> Self {
+        Self(GL::POINTS)
+    }
+
+    pub fn lines() -> Self {
+        Self(GL::LINES)
+    }
+
+    pub fn line_strip() -> Self {
+        Self(GL::LINE_STRIP)
+    }
+
+    pub fn line_loop() -> Self {
+        Self(GL::LINE_LOOP)
+    }
+
+    pub fn triangles() -> Self {
+        Self(GL::TRIANGLES)
+    }
+
+    pub fn triangle_strip() -> Self {
+        Self(GL::TRIANGLE_STRIP)
+    }
+
+    pub fn triangle_fan() -> Self {
+        Self(GL::TRIANGLE_FAN)
+    }
+}
+
+impl From<MeshMode> for u32 {
+    fn from(mode: MeshMode) -> Self {
+        mode.0
+    }
+}
+
+/// Range of indices (or vertices for non-indexed meshes) drawn on its own, usually one per material.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Submesh {
+    pub first: usize,
+    pub count: usize,
+    pub mode: MeshMode,
+}
+
+impl Submesh {
+    pub fn new(first: usize, count: usize) -> Self {
+        Self { first, count, mode: MeshMode::triangles() }
+    }
+
+    /// Same restrictions as `Mesh::with_mode` apply to split indices.
+    pub fn with_mode(mut self, mode: MeshMode) -> Self {
+        self.mode = mode;
+        self
+    }
+}
+
+fn vertex_arrays(ctx: &Ctx) -> Option<HashMap<ProgramId, RecordedArrays>> {
+    ctx.has_extension("OES_vertex_array_object").then(HashMap::new)
+}
+
+/// With `OES_vertex_array_object` attribute bindings are recorded into a vertex array (one per
+/// index buffer of a split mesh) the first time mesh is drawn by a program, later draws with that
+/// program only bind it. Without the extension attributes are bound on every draw.
 pub struct Mesh {
     ctx: Ctx,
     mode: MeshMode,
-    vertex_buffers: HashMap<&'static str, VertexBuffer>,
-    element_buffer: ElementBuffer,
+    vertex_buffers: HashMap<&'static str, (Rc<VertexBuffer>, VertexAttribute)>,
+    element_buffers: Vec<ElementBuffer>,
+    submeshes: Vec<Submesh>,
+    instance_count: Option<usize>,
+    /// Per program vertex arrays, `None` without `OES_vertex_array_object`.
+    vertex_arrays: Option<HashMap<ProgramId, RecordedArrays>>,
+    usage: BufferUsage,
+    split_indices: bool,
 }
 
 impl Mesh {
-    pub fn new(ctx: &Ctx, indices: &[u16]) -> Result<Self, String> {
-        let data = indices.iter().flat_map(|e| e.to_ne_bytes()).collect::<Vec<u8>>();
-        let eb = ElementBuffer::new(ctx, 2, &data)?;
+    pub fn new(ctx: &Ctx, indices: &[u16]) -> Result<Self, Error> {
+        Self::from_indices(ctx, indices)
+    }
 
+    /// `u32` indices require `OES_element_index_uint`, `Error::MissingExtension` is returned without it.
+    pub fn from_indices<I: Index>(ctx: &Ctx, indices: &[I]) -> Result<Self, Error> {
+        Self::with_element_buffers(ctx, element_buffers(ctx, indices, false)?, false)
+    }
+
+    /// Same as `from_indices`, but without `OES_element_index_uint` indices are split into
+    /// several `u16` index buffers drawn one after another, which only works for points, lines
+    /// and triangles, not for strips, loops or fans. Consecutive primitives spanning less than
+    /// 65536 vertices share a buffer, indices jumping back and forth between distant vertices
+    /// end up in many small ones.
+    pub fn from_indices_or_split(ctx: &Ctx, indices: &[u32]) -> Result<Self, Error> {
+        Self::with_element_buffers(ctx, element_buffers(ctx, indices, true)?, true)
+    }
+
+    /// Mesh drawn with `draw_arrays`, vertices are taken in order as many as the shortest attribute has.
+    pub fn non_indexed(ctx: &Ctx) -> Self {
+        Self {
+            ctx: ctx.clone(),
+            mode: MeshMode::triangles(),
+            vertex_buffers: HashMap::new(),
+            element_buffers: vec![],
+            submeshes: vec![],
+            instance_count: None,
+            vertex_arrays: vertex_arrays(ctx),
+            usage: BufferUsage::Static,
+            split_indices: false,
+        }
+    }
+
+    /// Like `from_vertices`, without index buffer.
+    pub fn from_vertices_non_indexed<V: Vertex>(ctx: &Ctx, vertices: &[V]) -> Result<Self, Error> {
+        Self::non_indexed(ctx).with_vertices(vertices)
+    }
+
+    fn with_element_buffers(ctx: &Ctx, element_buffers: Vec<ElementBuffer>, split_indices: bool) -> Result<Self, Error> {
         Ok(Self {
             ctx: ctx.clone(),
-            mode: MeshMode(GL::TRIANGLES),
+            mode: MeshMode::triangles(),
             vertex_buffers: HashMap::new(),
-            element_buffer: eb
+            element_buffers,
+            submeshes: vec![],
+            instance_count: None,
+            vertex_arrays: vertex_arrays(ctx),
+            usage: BufferUsage::Static,
+            split_indices,
         })
     }
 
-    pub fn with_attribute<T: Attribute>(mut self, name: &'static str, data: &T::Repr) -> Result<Self, String> {
-        let vb = VertexBuffer::new::<T>(&self.ctx, T::new(name), data)?;
-        self.vertex_buffers.insert(name, vb);
+    /// Uploads all vertices into a single buffer, each field is bound with vertex stride and its offset.
+    /// Buffer is static, for other usage hints chain `with_usage` and `with_vertices` instead.
+    pub fn from_vertices<V: Vertex, I: Index>(ctx: &Ctx, vertices: &[V], indices: &[I]) -> Result<Self, Error> {
+        Self::from_indices(ctx, indices)?.with_vertices(vertices)
+    }
+
+    /// Interleaved vertices uploaded with the usage hint set so far, e.g.
+    /// `Mesh::from_indices(&ctx, &indices)?.with_usage(BufferUsage::Dynamic).with_vertices(&vertices)?`.
+    pub fn with_vertices<V: Vertex>(mut self, vertices: &[V]) -> Result<Self, Error> {
+        let stride = std::mem::size_of::<V>() as i32;
+        let vb = Rc::new(VertexBuffer::new(&self.ctx, vertex_bytes(vertices), stride, self.usage)?);
+        for att in V::attributes() {
+            self.vertex_buffers.insert(att.name, (vb.clone(), att));
+        }
+        self.invalidate_vertex_arrays();
         Ok(self)
     }
 
-    pub fn draw(&mut self, program: &Program) -> Result<(), String> {
-        let mut enabled_attribs = vec![];
-        for (&at, buf) in self.vertex_buffers.iter_mut() {
-            if let Some(idx) = Some(self.ctx.get_attrib_location(&program.program, at))
-                .filter(|idx| *idx >= 0)
-                .map(|idx| idx as u32) {
-                    self.ctx.enable_vertex_attrib_array(idx as u32);
-                    enabled_attribs.push(idx);
-                    buf.bind(idx);
+    /// Indices split by `from_indices_or_split` into several buffers can only be drawn as points,
+    /// lines or triangles, drawing them with other modes fails with `Error::MissingExtension`.
+    pub fn with_mode(mut self, mode: MeshMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn mode(&self) -> MeshMode {
+        self.mode
+    }
+
+    /// Adds a range that can be drawn with `draw_submesh`, index of the submesh is its insertion order.
+    pub fn with_submesh(mut self, submesh: Submesh) -> Self {
+        self.submeshes.push(submesh);
+        self
+    }
+
+    pub fn submeshes(&self) -> &[Submesh] {
+        &self.submeshes
+    }
+
+    /// Usage hint for attribute buffers added after this call.
+    pub fn with_usage(mut self, usage: BufferUsage) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    pub fn with_attribute<T: Attribute>(mut self, name: &'static str, data: &T::Repr) -> Result<Self, Error> {
+        let vb = VertexBuffer::new(&self.ctx, &T::pack(data), 0, self.usage)?;
+        self.vertex_buffers.insert(name, (Rc::new(vb), T::new(name).layout(name)));
+        self.invalidate_vertex_arrays();
+        Ok(self)
+    }
+
+    /// Per-instance attribute, value advances once every `divisor` instances rather than per vertex.
+    /// Mesh with instance attributes is drawn instanced, requires `ANGLE_instanced_arrays`.
+    pub fn with_instance_attribute<T: Attribute>(mut self, name: &'static str, data: &T::Repr, divisor: u32) -> Result<Self, Error> {
+        self.ctx.require_extension("ANGLE_instanced_arrays")?;
+        let vb = VertexBuffer::new(&self.ctx, &T::pack(data), 0, self.usage)?.per_instance(divisor.max(1));
+        self.vertex_buffers.insert(name, (Rc::new(vb), T::new(name).layout(name)));
+        self.invalidate_vertex_arrays();
+        Ok(self)
+    }
+
+    /// Interleaved per-instance attributes, like `from_vertices` but advancing once every `divisor` instances.
+    pub fn with_instances<V: Vertex>(mut self, instances: &[V], divisor: u32) -> Result<Self, Error> {
+        self.ctx.require_extension("ANGLE_instanced_arrays")?;
+        let stride = std::mem::size_of::<V>() as i32;
+        let vb = VertexBuffer::new(&self.ctx, vertex_bytes(instances), stride, self.usage)?.per_instance(divisor.max(1));
+        let vb = Rc::new(vb);
+        for att in V::attributes() {
+            self.vertex_buffers.insert(att.name, (vb.clone(), att));
+        }
+        self.invalidate_vertex_arrays();
+        Ok(self)
+    }
+
+    /// Overrides number of instances drawn, by default it is as many as the shortest instance attribute covers.
+    pub fn with_instance_count(mut self, count: usize) -> Result<Self, Error> {
+        self.ctx.require_extension("ANGLE_instanced_arrays")?;
+        self.instance_count = Some(count);
+        Ok(self)
+    }
+
+    /// Number of instances drawn, `None` when mesh is not instanced.
+    pub fn instance_count(&self) -> Option<usize> {
+        self.instance_count.or_else(|| {
+            self.vertex_buffers
+                .values()
+                .filter(|(buf, _)| buf.divisor != 0)
+                .map(|(buf, att)| buf.instance_count(att))
+                .min()
+        })
+    }
+
+    /// Replaces attribute data, buffer grows if new data is larger and vertex count follows new data.
+    pub fn update_attribute<T: Attribute>(&mut self, name: &'static str, data: &T::Repr) -> Result<(), Error> {
+        let (vb, _) = self.non_interleaved(name)?;
+        vb.buffer.replace(&T::pack(data));
+        Ok(())
+    }
+
+    /// Overwrites attribute values starting from vertex `first`, values must fit into current ones.
+    pub fn update_attribute_range<T: Attribute>(&mut self, name: &'static str, first: usize, data: &T::Repr) -> Result<(), Error> {
+        let (vb, att) = self.non_interleaved(name)?;
+        vb.buffer.write(first * att.column_size() * att.columns as usize, &T::pack(data))
+    }
+
+    fn non_interleaved(&self, name: &'static str) -> Result<&(Rc<VertexBuffer>, VertexAttribute), Error> {
+        let entry = self.vertex_buffers
+            .get(name)
+            .ok_or_else(|| Error::MissingAttribute(name.to_string()))?;
+        if entry.0.stride != 0 {
+            return Err(Error::BufferUpdate(format!("{} is interleaved, use update_vertices", name)));
+        }
+        Ok(entry)
+    }
+
+    /// Overwrites interleaved vertices starting from vertex `first`, vertices must fit into uploaded ones,
+    /// upload them again with `with_vertices` to change vertex count.
+    pub fn update_vertices<V: Vertex>(&mut self, first: usize, vertices: &[V]) -> Result<(), Error> {
+        let stride = std::mem::size_of::<V>();
+        let name = V::attributes().first().map(|a| a.name).unwrap_or_default();
+        let (vb, _) = self.vertex_buffers
+            .get(name)
+            .ok_or_else(|| Error::MissingAttribute(name.to_string()))?;
+        if vb.stride as usize != stride {
+            return Err(Error::BufferUpdate(format!("{} was not uploaded with this vertex layout", name)));
+        }
+        vb.buffer.write(first * stride, vertex_bytes(vertices))
+    }
+
+    /// Replaces indices, buffer is reused when index type stays the same.
+    pub fn update_indices<I: Index>(&mut self, indices: &[I]) -> Result<(), Error> {
+        match &mut self.element_buffers[..] {
+            // a split chunk is offset to its base vertex, new indices start from zero
+            [eb] if eb.data_type == I::DATA_TYPE && eb.base_vertex == 0 => {
+                eb.update(&index_bytes(indices));
+                Ok(())
+            }
+            _ => {
+                self.element_buffers = element_buffers(&self.ctx, indices, self.split_indices)?;
+                self.invalidate_vertex_arrays();
+                Ok(())
+            }
+        }
+    }
+
+    /// Number of vertices in the shortest per-vertex attribute buffer.
+    pub fn vertex_count(&self) -> usize {
+        self.vertex_buffers
+            .values()
+            .filter(|(buf, _)| buf.divisor == 0)
+            .map(|(buf, att)| buf.vertex_count(att))
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Number of indices, or vertices for non-indexed mesh.
+    pub fn element_count(&self) -> usize {
+        if self.element_buffers.is_empty() {
+            self.vertex_count()
+        } else {
+            self.element_buffers.iter().map(|eb| eb.num_elements).sum()
+        }
+    }
+
+    pub fn draw(&mut self, program: &Program) -> Result<(), Error> {
+        self.draw_checked(program, program.is_strict(), None)
+    }
+
+    /// Draws only the range of submesh `index`, buffers stay shared with every other submesh.
+    pub fn draw_submesh(&mut self, program: &Program, index: usize) -> Result<(), Error> {
+        self.draw_checked(program, program.is_strict(), Some(index))
+    }
+
+    /// Attributes the mesh provides but program does not use are fine in strict mode too,
+    /// the same mesh is often drawn by programs that need only part of its data.
+    pub(crate) fn draw_checked(&mut self, program: &Program, strict: bool, submesh: Option<usize>) -> Result<(), Error> {
+        let range = match submesh {
+            Some(index) => *self.submeshes.get(index).ok_or(Error::UnknownSubmesh(index))?,
+            None => Submesh::new(0, self.element_count()).with_mode(self.mode),
+        };
+        let list = [GL::POINTS, GL::LINES, GL::TRIANGLES].contains(&range.mode.0);
+        if self.element_buffers.len() > 1 && !list {
+            return Err(Error::MissingExtension("OES_element_index_uint"));
+        }
+
+        if strict {
+            for attribute in program.attributes() {
+                let (_, att) = self.vertex_buffers
+                    .get(attribute.name.as_str())
+                    .ok_or_else(|| Error::MissingAttribute(attribute.name.clone()))?;
+                if att.gl_type() != attribute.gl_type {
+                    return Err(Error::TypeMismatch {
+                        name: attribute.name.clone(),
+                        expected: attribute.type_name(),
+                        found: type_name(att.gl_type()),
+                    });
                 }
+            }
+        }
 
+        let instances = self.instance_count();
+        if self.vertex_arrays.is_some() {
+            return self.draw_recorded(program, range, instances);
         }
-        self.element_buffer.draw(self.mode);
 
+        let (bound, enabled_attribs, instanced_attribs) = self.enable_attributes(program);
+        for (chunk, first, count) in self.chunks(range) {
+            self.bind_chunk(&bound, chunk);
+            self.draw_chunk(chunk, range.mode, first, count, instances);
+        }
+
+        // divisors stick to attribute index, the next program might use it for per-vertex data
+        for idx in instanced_attribs.into_iter() {
+            self.ctx.vertex_attrib_divisor(idx, 0);
+        }
         for idx in enabled_attribs.into_iter() {
             self.ctx.disable_vertex_attrib_array(idx);
         }
 
         Ok(())
     }
+
+    fn draw_recorded(&mut self, program: &Program, range: Submesh, instances: Option<usize>) -> Result<(), Error> {
+        let identity = Rc::as_ptr(&program.identity);
+        let recorded = self.vertex_arrays
+            .as_ref()
+            .and_then(|v| v.get(&program.program))
+            .is_some_and(|r| r.program.as_ptr() == identity);
+
+        if !recorded {
+            let arrays = self.record_vertex_arrays(program)?;
+            if let Some(vertex_arrays) = self.vertex_arrays.as_mut() {
+                // arrays of dropped programs are released along the way
+                vertex_arrays.retain(|_, r| r.program.strong_count() > 0);
+                vertex_arrays.insert(program.program, RecordedArrays { program: Rc::downgrade(&program.identity), arrays });
+            }
+        }
+
+        let arrays = &self.vertex_arrays.as_ref().and_then(|v| v.get(&program.program)).expect("recorded above").arrays;
+        for (chunk, first, count) in self.chunks(range) {
+            self.ctx.bind_vertex_array(Some(arrays[chunk.unwrap_or(0)].id));
+            self.draw_chunk(chunk, range.mode, first, count, instances);
+        }
+        self.ctx.bind_vertex_array(None);
+
+        Ok(())
+    }
+
+    /// Vertex array per element buffer, or a single one for non-indexed mesh.
+    fn record_vertex_arrays(&self, program: &Program) -> Result<Vec<VertexArray>, Error> {
+        let chunks = match self.element_buffers.len() {
+            0 => vec![None],
+            n => (0..n).map(Some).collect(),
+        };
+        let arrays = chunks
+            .into_iter()
+            .map(|chunk| {
+                let vertex_array = VertexArray::new(&self.ctx)?;
+                self.ctx.bind_vertex_array(Some(vertex_array.id));
+                let (bound, _, _) = self.enable_attributes(program);
+                self.bind_chunk(&bound, chunk);
+                Ok(vertex_array)
+            })
+            .collect();
+        self.ctx.bind_vertex_array(None);
+        arrays
+    }
+
+    /// Enables arrays of attributes used by `program`, returns attributes to bind along with
+    /// enabled array indices and those of them that are per-instance.
+    fn enable_attributes(&self, program: &Program) -> (Vec<Binding<'_>>, Vec<u32>, Vec<u32>) {
+        let mut bound = vec![];
+        let mut enabled_attribs = vec![];
+        let mut instanced_attribs = vec![];
+        for (&at, (buf, att)) in self.vertex_buffers.iter() {
+            if let Some(idx) = program.attribute_location(at) {
+                for column in 0..att.columns as u32 {
+                    self.ctx.enable_vertex_attrib_array(idx + column);
+                    enabled_attribs.push(idx + column);
+                    if buf.divisor != 0 {
+                        instanced_attribs.push(idx + column);
+                    }
+                }
+                bound.push((idx, buf.as_ref(), att));
+            }
+        }
+        (bound, enabled_attribs, instanced_attribs)
+    }
+
+    /// Element buffers holding part of `range` along with that part relative to the buffer,
+    /// non-indexed mesh has a single `None` chunk. Split index buffers hold consecutive parts of
+    /// the original indices.
+    fn chunks(&self, range: Submesh) -> Vec<(Option<usize>, usize, usize)> {
+        if self.element_buffers.is_empty() {
+            return vec![(None, range.first, range.count)];
+        }
+        let mut chunks = vec![];
+        let mut start = 0;
+        for (chunk, eb) in self.element_buffers.iter().enumerate() {
+            let end = start + eb.num_elements;
+            let from = range.first.max(start);
+            let to = (range.first + range.count).min(end);
+            if from < to {
+                chunks.push((Some(chunk), from - start, to - from));
+            }
+            start = end;
+        }
+        chunks
+    }
+
+    /// Points attributes at vertices of `chunk` and binds its element buffer.
+    fn bind_chunk(&self, bound: &[Binding<'_>], chunk: Option<usize>) {
+        let eb = chunk.map(|c| &self.element_buffers[c]);
+        for (idx, buf, att) in bound.iter() {
+            buf.bind(*idx, att, eb.map_or(0, |eb| eb.base_vertex));
+        }
+        if let Some(eb) = eb {
+            eb.buffer.bind();
+        }
+    }
+
+    fn draw_chunk(&self, chunk: Option<usize>, mode: MeshMode, first: usize, count: usize, instances: Option<usize>) {
+        match (chunk, instances) {
+            (Some(c), _) => self.element_buffers[c].draw(mode, first, count, instances),
+            (None, Some(n)) => self.ctx.draw_arrays_instanced(mode.into(), first as i32, count as i32, n as i32),
+            (None, None) => self.ctx.draw_arrays(mode.into(), first as i32, count as i32),
+        }
+    }
+
+    fn invalidate_vertex_arrays(&mut self) {
+        if let Some(vertex_arrays) = self.vertex_arrays.as_mut() {
+            vertex_arrays.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attributes::{AttributeMatrix4, AttributeScalar, AttributeShort, AttributeUShort, AttributeVector2};
+    use crate::backend::MockBackend;
+    use crate::backend::mock::Call;
+
+    const VERTEX: &str = "attribute vec2 position; attribute float weight; void main() {}";
+
+    fn setup(mock: MockBackend) -> (MockBackend, Ctx, Program) {
+        let ctx = Ctx::with_backend(mock.clone()).unwrap();
+        let program = Program::new(&ctx, VERTEX, "void main() {}").unwrap();
+        (mock, ctx, program)
+    }
+
+    fn triangle(ctx: &Ctx) -> Mesh {
+        Mesh::new(ctx, &[0, 1, 2]).unwrap()
+            .with_attribute::<AttributeVector2>("position", &[[0., 0.], [1., 0.], [0., 1.]]).unwrap()
+            .with_attribute::<AttributeScalar>("weight", &[1., 1., 1.]).unwrap()
+    }
+
+    fn location(program: &Program, name: &str) -> u32 {
+        program.attribute_location(name).unwrap()
+    }
+
+    #[test]
+    fn attributes_are_enabled_for_draw_only() {
+        let (mock, ctx, program) = setup(MockBackend::new().without_extension("OES_vertex_array_object"));
+        let mut mesh = triangle(&ctx);
+        mock.take_calls();
+
+        mesh.draw(&program).unwrap();
+
+        let calls = mock.take_calls();
+        for name in ["position", "weight"] {
+            assert!(calls.contains(&Call::EnableVertexAttribArray(location(&program, name))));
+            assert!(calls.contains(&Call::DisableVertexAttribArray(location(&program, name))));
+        }
+        assert!(calls.iter().any(|c| matches!(
+            c,
+            Call::VertexAttribPointer { index, size: 2, data_type: GL::FLOAT, .. } if *index == location(&program, "position")
+        )));
+        assert!(mock.enabled_attrib_arrays().is_empty());
+    }
+
+    #[test]
+    fn attributes_unused_by_program_are_not_enabled() {
+        let (mock, ctx, _) = setup(MockBackend::new().without_extension("OES_vertex_array_object"));
+        let program = Program::new(&ctx, "attribute vec2 position; void main() {}", "void main() {}").unwrap();
+        let mut mesh = triangle(&ctx);
+        mock.take_calls();
+
+        mesh.draw(&program).unwrap();
+
+        let enabled = mock.take_calls().into_iter().filter(|c| matches!(c, Call::EnableVertexAttribArray(_))).count();
+        assert_eq!(enabled, 1);
+    }
+
+    #[test]
+    fn strict_draw_reports_missing_attribute() {
+        let (_, ctx, program) = setup(MockBackend::new());
+        let mut mesh = Mesh::new(&ctx, &[0, 1, 2]).unwrap()
+            .with_attribute::<AttributeVector2>("position", &[[0., 0.], [1., 0.], [0., 1.]]).unwrap();
+
+        let result = mesh.draw(&program.with_strict(true));
+
+        assert_eq!(result, Err(Error::MissingAttribute("weight".to_string())));
+    }
+
+    fn split_setup() -> (MockBackend, Ctx, Program) {
+        let mock = MockBackend::new()
+            .without_extension("OES_element_index_uint")
+            .without_extension("OES_vertex_array_object");
+        setup(mock)
+    }
+
+    fn positions(mesh: Mesh, count: usize) -> Mesh {
+        mesh.with_attribute::<AttributeVector2>("position", &vec![[0., 0.]; count]).unwrap()
+    }
+
+    #[test]
+    fn split_mesh_draws_lists_only() {
+        let (mock, ctx, program) = split_setup();
+        let indices = [0, 1, 2, 0, 1, 2, 70_000, 70_001, 70_002, 70_000, 70_001, 70_002];
+        let mut mesh = positions(Mesh::from_indices_or_split(&ctx, &indices).unwrap(), 70_003);
+        mock.take_calls();
+
+        mesh.draw(&program).unwrap();
+        let draws = mock.take_calls().into_iter().filter(|c| matches!(c, Call::DrawElements { count: 6, .. })).count();
+        assert_eq!(draws, 2);
+
+        let mut strip = mesh.with_mode(MeshMode::triangle_strip());
+        assert_eq!(strip.draw(&program), Err(Error::MissingExtension("OES_element_index_uint")));
+    }
+
+    #[test]
+    fn updated_indices_drop_base_vertex_of_split_chunk() {
+        let (mock, ctx, program) = split_setup();
+        let mut mesh = positions(Mesh::from_indices_or_split(&ctx, &[70_000, 70_001, 70_002]).unwrap(), 70_003);
+
+        mesh.update_indices::<u16>(&[0, 1, 2]).unwrap();
+        mock.take_calls();
+        mesh.draw(&program).unwrap();
+
+        let position = location(&program, "position");
+        assert!(mock.take_calls().iter().any(|c| matches!(
+            c,
+            Call::VertexAttribPointer { index, offset: 0, .. } if *index == position
+        )));
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct Particle {
+        position: [f32; 2],
+        weight: f32,
+    }
+
+    unsafe impl Vertex for Particle {
+        fn attributes() -> Vec<VertexAttribute> {
+            vec![
+                VertexAttribute { name: "position", size: 2, data_type: GL::FLOAT, normalized: false, offset: 0, columns: 1 },
+                VertexAttribute { name: "weight", size: 1, data_type: GL::FLOAT, normalized: false, offset: 8, columns: 1 },
+            ]
+        }
+    }
+
+    #[test]
+    fn interleaved_vertices_follow_usage_hint() {
+        let (mock, ctx, _) = setup(MockBackend::new());
+        let particle = Particle { position: [0., 0.], weight: 1. };
+        mock.take_calls();
+
+        let mesh = Mesh::non_indexed(&ctx).with_usage(BufferUsage::Stream).with_vertices(&[particle; 3]).unwrap();
+
+        let calls = mock.take_calls();
+        assert!(calls.contains(&Call::BufferData { target: GL::ARRAY_BUFFER, size: 36, usage: GL::STREAM_DRAW }));
+        assert_eq!(mesh.vertex_count(), 3);
+    }
+
+    #[test]
+    fn vertex_array_records_attributes_once() {
+        let (mock, ctx, program) = setup(MockBackend::new());
+        let mut mesh = triangle(&ctx);
+
+        mesh.draw(&program).unwrap();
+        mock.take_calls();
+        mesh.draw(&program).unwrap();
+
+        let calls = mock.take_calls();
+        assert!(!calls.iter().any(|c| matches!(c, Call::EnableVertexAttribArray(_) | Call::VertexAttribPointer { .. })));
+        assert!(calls.iter().any(|c| matches!(c, Call::DrawElements { count: 3, .. })));
+        assert_eq!(mock.current_vertex_array(), None);
+        assert_eq!(mock.live_vertex_arrays(), 1);
+    }
+
+    #[test]
+    fn vertex_array_extension_is_queried_once() {
+        let (mock, ctx, _) = setup(MockBackend::new());
+        mock.take_calls();
+
+        triangle(&ctx);
+        triangle(&ctx);
+
+        let queries = mock.take_calls().into_iter().filter(|c| *c == Call::GetExtension("OES_vertex_array_object".to_string())).count();
+        assert_eq!(queries, 1);
+    }
+
+    #[test]
+    fn vertex_arrays_of_dropped_programs_are_released() {
+        let (mock, ctx, program) = setup(MockBackend::new());
+        let mut mesh = triangle(&ctx);
+
+        mesh.draw(&program).unwrap();
+        drop(program);
+        let other = Program::new(&ctx, VERTEX, "void main() {}").unwrap();
+        mesh.draw(&other).unwrap();
+
+        assert_eq!(mock.live_vertex_arrays(), 1);
+    }
+
+    #[test]
+    fn split_mesh_records_vertex_array_per_chunk() {
+        let (mock, ctx, program) = setup(MockBackend::new().without_extension("OES_element_index_uint"));
+        let indices = [0, 1, 2, 0, 1, 2, 70_000, 70_001, 70_002, 70_000, 70_001, 70_002];
+        let mut mesh = positions(Mesh::from_indices_or_split(&ctx, &indices).unwrap(), 70_003);
+
+        mesh.draw(&program).unwrap();
+        mock.take_calls();
+        mesh.draw(&program).unwrap();
+
+        let calls = mock.take_calls();
+        assert!(!calls.iter().any(|c| matches!(c, Call::VertexAttribPointer { .. } | Call::BindBuffer(..))));
+        assert_eq!(calls.iter().filter(|c| matches!(c, Call::DrawElements { count: 6, .. })).count(), 2);
+        assert_eq!(mock.live_vertex_arrays(), 2);
+    }
+
+    #[test]
+    fn quantized_and_matrix_attributes_are_pointed_with_their_layout() {
+        let (mock, ctx, _) = setup(MockBackend::new());
+        let vertex = "attribute vec2 uv; attribute vec4 weights; attribute mat4 model; void main() {}";
+        let program = Program::new(&ctx, vertex, "void main() {}").unwrap();
+        let mut mesh = Mesh::new(&ctx, &[0, 1, 2]).unwrap()
+            .with_attribute::<AttributeShort<2>>("uv", &[[0, 0], [i16::MAX, 0], [0, i16::MAX]]).unwrap()
+            .with_attribute::<AttributeUShort<4>>("weights", &[[u16::MAX, 0, 0, 0]; 3]).unwrap()
+            .with_attribute::<AttributeMatrix4>("model", &[[0.; 16]; 3]).unwrap();
+        mock.take_calls();
+
+        mesh.draw(&program).unwrap();
+
+        let pointers = mock.take_calls()
+            .into_iter()
+            .filter_map(|c| match c {
+                Call::VertexAttribPointer { index, size, data_type, normalized, stride, offset } =>
+                    Some((index, (size, data_type, normalized, stride, offset))),
+                _ => None,
+            })
+            .collect::<HashMap<_, _>>();
+        assert_eq!(pointers[&location(&program, "uv")], (2, GL::SHORT, true, 0, 0));
+        assert_eq!(pointers[&location(&program, "weights")], (4, GL::UNSIGNED_SHORT, true, 0, 0));
+        let model = location(&program, "model");
+        for column in 0..4 {
+            assert_eq!(pointers[&(model + column)], (4, GL::FLOAT, false, 64, column as i32 * 16));
+        }
+    }
+
+    fn buffer_writes(calls: &[Call]) -> Vec<Call> {
+        calls.iter().filter(|c| matches!(c, Call::BufferData { .. } | Call::BufferSubData { .. })).cloned().collect()
+    }
+
+    fn drawn_vertices(mock: &MockBackend) -> Vec<i32> {
+        mock.take_calls().into_iter().filter_map(|c| match c {
+            Call::DrawArrays { count, .. } => Some(count),
+            _ => None,
+        }).collect()
+    }
+
+    #[test]
+    fn replaced_attribute_grows_or_shrinks_vertex_count() {
+        let (mock, ctx, program) = setup(MockBackend::new());
+        let mut mesh = positions(Mesh::non_indexed(&ctx), 3);
+        mock.take_calls();
+
+        mesh.update_attribute::<AttributeVector2>("position", &[[0., 0.]; 6]).unwrap();
+        assert_eq!(
+            buffer_writes(&mock.take_calls()),
+            [Call::BufferData { target: GL::ARRAY_BUFFER, size: 48, usage: GL::STATIC_DRAW }],
+        );
+
+        mesh.update_attribute::<AttributeVector2>("position", &[[0., 0.]; 2]).unwrap();
+        assert_eq!(
+            buffer_writes(&mock.take_calls()),
+            [Call::BufferSubData { target: GL::ARRAY_BUFFER, offset: 0, size: 16 }],
+        );
+        mesh.draw(&program).unwrap();
+        assert_eq!(drawn_vertices(&mock), [2]);
+    }
+
+    #[test]
+    fn streamed_attribute_is_orphaned_on_replace() {
+        let (mock, ctx, _) = setup(MockBackend::new());
+        let mut mesh = positions(Mesh::non_indexed(&ctx).with_usage(BufferUsage::Stream), 3);
+        mock.take_calls();
+
+        mesh.update_attribute::<AttributeVector2>("position", &[[0., 0.]; 2]).unwrap();
+
+        assert_eq!(
+            buffer_writes(&mock.take_calls()),
+            [Call::BufferData { target: GL::ARRAY_BUFFER, size: 16, usage: GL::STREAM_DRAW }],
+        );
+        assert_eq!(mesh.vertex_count(), 2);
+    }
+
+    #[test]
+    fn ranged_attribute_update_keeps_vertex_count() {
+        let (mock, ctx, program) = setup(MockBackend::new());
+        let mut mesh = positions(Mesh::non_indexed(&ctx), 3);
+        mock.take_calls();
+
+        mesh.update_attribute_range::<AttributeVector2>("position", 1, &[[1., 1.]; 2]).unwrap();
+        assert_eq!(
+            buffer_writes(&mock.take_calls()),
+            [Call::BufferSubData { target: GL::ARRAY_BUFFER, offset: 8, size: 16 }],
+        );
+        mesh.draw(&program).unwrap();
+        assert_eq!(drawn_vertices(&mock), [3]);
+
+        let overflow = mesh.update_attribute_range::<AttributeVector2>("position", 2, &[[1., 1.]; 2]);
+        assert!(matches!(overflow, Err(Error::BufferUpdate(_))));
+    }
+
+    #[test]
+    fn vertex_update_writes_range_and_never_grows() {
+        let (mock, ctx, _) = setup(MockBackend::new());
+        let particle = Particle { position: [0., 0.], weight: 1. };
+        let mut mesh = Mesh::non_indexed(&ctx).with_vertices(&[particle; 3]).unwrap();
+        mock.take_calls();
+
+        mesh.update_vertices(1, &[particle; 2]).unwrap();
+        assert_eq!(
+            buffer_writes(&mock.take_calls()),
+            [Call::BufferSubData { target: GL::ARRAY_BUFFER, offset: 12, size: 24 }],
+        );
+
+        mesh.update_vertices(0, &[particle; 3]).unwrap();
+        assert_eq!(
+            buffer_writes(&mock.take_calls()),
+            [Call::BufferData { target: GL::ARRAY_BUFFER, size: 36, usage: GL::STATIC_DRAW }],
+        );
+
+        assert!(matches!(mesh.update_vertices(0, &[particle; 4]), Err(Error::BufferUpdate(_))));
+        assert_eq!(mesh.vertex_count(), 3);
+    }
+
+    #[test]
+    fn split_chunks_follow_index_window_in_any_order() {
+        let descending = [10, 11, 12, 5, 6, 7, 0, 1, 2];
+        assert_eq!(split_indices(&descending).unwrap(), [(0, vec![10, 11, 12, 5, 6, 7, 0, 1, 2])]);
+
+        let alternating = [0, 1, 2, 0, 1, 2, 70_000, 70_001, 70_002, 70_000, 70_001, 70_002, 3, 4, 5, 3, 4, 5];
+        let bases = split_indices(&alternating).unwrap().into_iter().map(|(base, _)| base).collect::<Vec<_>>();
+        assert_eq!(bases, [0, 70_000, 3]);
+    }
+
+    #[test]
+    fn non_indexed_mesh_draws_arrays_in_its_mode() {
+        let (mock, ctx, program) = setup(MockBackend::new());
+        let mesh = positions(Mesh::non_indexed(&ctx), 4);
+        let mut lines = mesh.with_mode(MeshMode::lines());
+        mock.take_calls();
+
+        lines.draw(&program).unwrap();
+
+        let draws = mock.take_calls()
+            .into_iter()
+            .filter(|c| matches!(c, Call::DrawArrays { .. } | Call::DrawElements { .. }))
+            .collect::<Vec<_>>();
+        assert_eq!(draws, [Call::DrawArrays { mode: GL::LINES, first: 0, count: 4 }]);
+    }
+
+    #[test]
+    fn instance_attribute_draws_instanced_and_resets_divisor() {
+        let (mock, ctx, _) = setup(MockBackend::new().without_extension("OES_vertex_array_object"));
+        let vertex = "attribute vec2 position; attribute vec2 offset; void main() {}";
+        let program = Program::new(&ctx, vertex, "void main() {}").unwrap();
+        let mut mesh = positions(Mesh::new(&ctx, &[0, 1, 2]).unwrap(), 3)
+            .with_instance_attribute::<AttributeVector2>("offset", &[[0., 0.]; 4], 1).unwrap();
+        mock.take_calls();
+
+        mesh.draw(&program).unwrap();
+
+        let offset = location(&program, "offset");
+        let calls = mock.take_calls()
+            .into_iter()
+            .filter(|c| matches!(c, Call::VertexAttribDivisor { .. } | Call::DrawElementsInstanced { .. }))
+            .collect::<Vec<_>>();
+        assert_eq!(calls, [
+            Call::VertexAttribDivisor { index: offset, divisor: 1 },
+            Call::DrawElementsInstanced { mode: GL::TRIANGLES, count: 3, data_type: GL::UNSIGNED_SHORT, offset: 0, instances: 4 },
+            Call::VertexAttribDivisor { index: offset, divisor: 0 },
+        ]);
+    }
 }
@@ -0,0 +1,93 @@
+use crate::GL;
+use crate::Ctx;
+use crate::backend::{ProgramId, UniformLocation};
+
+/// Vertex attribute consumed by a linked program.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ActiveAttribute {
+    pub name: String,
+    /// GL type enum, e.g. `GL::FLOAT_VEC3`.
+    pub gl_type: u32,
+    pub size: i32,
+    pub location: u32,
+}
+
+/// Uniform used by a linked program, arrays are reported once under their base name.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ActiveUniform {
+    pub name: String,
+    /// GL type enum, e.g. `GL::SAMPLER_2D`.
+    pub gl_type: u32,
+    /// Number of elements, `1` for non array uniforms.
+    pub size: i32,
+    pub location: UniformLocation,
+}
+
+impl ActiveAttribute {
+    pub fn type_name(&self) -> &'static str {
+        type_name(self.gl_type)
+    }
+}
+
+impl ActiveUniform {
+    pub fn type_name(&self) -> &'static str {
+        type_name(self.gl_type)
+    }
+}
+
+/// GLSL spelling of a GL type enum.
+pub fn type_name(gl_type: u32) -> &'static str {
+    match gl_type {
+        GL::FLOAT => "float",
+        GL::FLOAT_VEC2 => "vec2",
+        GL::FLOAT_VEC3 => "vec3",
+        GL::FLOAT_VEC4 => "vec4",
+        GL::INT => "int",
+        GL::INT_VEC2 => "ivec2",
+        GL::INT_VEC3 => "ivec3",
+        GL::INT_VEC4 => "ivec4",
+        GL::BOOL => "bool",
+        GL::BOOL_VEC2 => "bvec2",
+        GL::BOOL_VEC3 => "bvec3",
+        GL::BOOL_VEC4 => "bvec4",
+        GL::FLOAT_MAT2 => "mat2",
+        GL::FLOAT_MAT3 => "mat3",
+        GL::FLOAT_MAT4 => "mat4",
+        GL::SAMPLER_2D => "sampler2D",
+        GL::SAMPLER_CUBE => "samplerCube",
+        _ => "unknown",
+    }
+}
+
+/// Built-in attributes such as `gl_VertexID` have no location and are skipped.
+pub(crate) fn attributes(ctx: &Ctx, program: ProgramId) -> Vec<ActiveAttribute> {
+    let count = ctx.get_program_parameter(program, GL::ACTIVE_ATTRIBUTES).max(0) as u32;
+    (0..count)
+        .filter_map(|i| ctx.get_active_attrib(program, i))
+        .filter_map(|info| {
+            let location = ctx.get_attrib_location(program, &info.name);
+            (location >= 0).then_some(ActiveAttribute {
+                name: info.name,
+                gl_type: info.gl_type,
+                size: info.size,
+                location: location as u32,
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn uniforms(ctx: &Ctx, program: ProgramId) -> Vec<ActiveUniform> {
+    let count = ctx.get_program_parameter(program, GL::ACTIVE_UNIFORMS).max(0) as u32;
+    (0..count)
+        .filter_map(|i| ctx.get_active_uniform(program, i))
+        .filter_map(|info| {
+            let name = info.name.strip_suffix("[0]").unwrap_or(&info.name).to_string();
+            ctx.get_uniform_location(program, &name).map(|location| ActiveUniform {
+                name,
+                gl_type: info.gl_type,
+                size: info.size,
+                location,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,468 @@
+use crate::{GL, Ctx};
+use crate::texture::Viewport;
+
+/// Blend factor, one of `GL::ZERO`, `GL::ONE`, `GL::SRC_ALPHA`, `GL::ONE_MINUS_SRC_ALPHA`, ...
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlendFactor(pub u32);
+
+/// `GL::FUNC_ADD`, `GL::FUNC_SUBTRACT` or `GL::FUNC_REVERSE_SUBTRACT`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlendEquation(pub u32);
+
+/// Comparison used by depth and stencil tests, `GL::LESS`, `GL::LEQUAL`, `GL::ALWAYS`, ...
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CompareFunc(pub u32);
+
+/// `GL::FRONT`, `GL::BACK` or `GL::FRONT_AND_BACK`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Face(pub u32);
+
+/// Winding of front facing triangles, `GL::CCW` or `GL::CW`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrontFace(pub u32);
+
+/// `GL::KEEP`, `GL::ZERO`, `GL::REPLACE`, `GL::INCR`, `GL::DECR`, `GL::INVERT`, ...
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StencilOp(pub u32);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Blend {
+    pub equation_rgb: BlendEquation,
+    pub equation_alpha: BlendEquation,
+    pub src_rgb: BlendFactor,
+    pub dst_rgb: BlendFactor,
+    pub src_alpha: BlendFactor,
+    pub dst_alpha: BlendFactor,
+    /// Used by `GL::CONSTANT_COLOR` and `GL::CONSTANT_ALPHA` factors.
+    pub color: [f32; 4],
+}
+
+impl Blend {
+    /// Same factors for color and alpha, added together.
+    pub fn new(src: BlendFactor, dst: BlendFactor) -> Self {
+        Self {
+            equation_rgb: BlendEquation(GL::FUNC_ADD),
+            equation_alpha: BlendEquation(GL::FUNC_ADD),
+            src_rgb: src,
+            dst_rgb: dst,
+            src_alpha: src,
+            dst_alpha: dst,
+            color: [0., 0., 0., 0.],
+        }
+    }
+
+    /// Straight alpha, `src * a + dst * (1 - a)`.
+    pub fn alpha() -> Self {
+        Self::new(BlendFactor(GL::SRC_ALPHA), BlendFactor(GL::ONE_MINUS_SRC_ALPHA))
+    }
+
+    /// Colors already multiplied by alpha.
+    pub fn premultiplied() -> Self {
+        Self::new(BlendFactor(GL::ONE), BlendFactor(GL::ONE_MINUS_SRC_ALPHA))
+    }
+
+    pub fn additive() -> Self {
+        Self::new(BlendFactor(GL::ONE), BlendFactor(GL::ONE))
+    }
+
+    pub fn with_alpha_factors(mut self, src: BlendFactor, dst: BlendFactor) -> Self {
+        self.src_alpha = src;
+        self.dst_alpha = dst;
+        self
+    }
+
+    pub fn with_equation(mut self, rgb: BlendEquation, alpha: BlendEquation) -> Self {
+        self.equation_rgb = rgb;
+        self.equation_alpha = alpha;
+        self
+    }
+
+    pub fn with_color(mut self, color: [f32; 4]) -> Self {
+        self.color = color;
+        self
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StencilFace {
+    pub func: CompareFunc,
+    pub reference: i32,
+    pub read_mask: u32,
+    /// Applied when stencil test fails.
+    pub fail: StencilOp,
+    /// Applied when stencil test passes but depth test fails.
+    pub depth_fail: StencilOp,
+    /// Applied when both tests pass.
+    pub pass: StencilOp,
+    pub write_mask: u32,
+}
+
+impl StencilFace {
+    /// Compares against `reference` with all bits, keeps stencil values as they are.
+    pub fn new(func: CompareFunc, reference: i32) -> Self {
+        Self {
+            func,
+            reference,
+            read_mask: !0,
+            fail: StencilOp(GL::KEEP),
+            depth_fail: StencilOp(GL::KEEP),
+            pass: StencilOp(GL::KEEP),
+            write_mask: !0,
+        }
+    }
+
+    pub fn with_ops(mut self, fail: StencilOp, depth_fail: StencilOp, pass: StencilOp) -> Self {
+        self.fail = fail;
+        self.depth_fail = depth_fail;
+        self.pass = pass;
+        self
+    }
+
+    pub fn with_masks(mut self, read_mask: u32, write_mask: u32) -> Self {
+        self.read_mask = read_mask;
+        self.write_mask = write_mask;
+        self
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Stencil {
+    pub front: StencilFace,
+    pub back: StencilFace,
+}
+
+impl Stencil {
+    pub fn new(face: StencilFace) -> Self {
+        Self { front: face, back: face }
+    }
+
+    pub fn separate(front: StencilFace, back: StencilFace) -> Self {
+        Self { front, back }
+    }
+}
+
+/// Fixed function state used by a `Pipeline`, `None` disables the corresponding test or stage.
+///
+/// Default matches the state `Ctx` starts with: depth test with `GL::LESS`, back faces culled,
+/// no blending, stencil or scissor.
+///
+/// ```ignore
+/// let ui = RenderState::default()
+///     .with_blend(Some(Blend::alpha()))
+///     .with_depth_test(None)
+///     .with_cull_face(None);
+///
+/// let mut pipeline = Pipeline::new(&ctx).with_render_state(ui);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RenderState {
+    pub blend: Option<Blend>,
+    pub depth_test: Option<CompareFunc>,
+    pub depth_write: bool,
+    pub cull_face: Option<Face>,
+    pub front_face: FrontFace,
+    pub color_mask: [bool; 4],
+    /// `[factor, units]` added to depth of filled polygons.
+    pub polygon_offset: Option<[f32; 2]>,
+    pub stencil: Option<Stencil>,
+    pub scissor: Option<Viewport>,
+}
+
+impl Default for RenderState {
+    fn default() -> Self {
+        Self {
+            blend: None,
+            depth_test: Some(CompareFunc(GL::LESS)),
+            depth_write: true,
+            cull_face: Some(Face(GL::BACK)),
+            front_face: FrontFace(GL::CCW),
+            color_mask: [true; 4],
+            polygon_offset: None,
+            stencil: None,
+            scissor: None,
+        }
+    }
+}
+
+impl RenderState {
+    pub fn with_blend(mut self, blend: Option<Blend>) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    pub fn with_depth_test(mut self, func: Option<CompareFunc>) -> Self {
+        self.depth_test = func;
+        self
+    }
+
+    pub fn with_depth_write(mut self, write: bool) -> Self {
+        self.depth_write = write;
+        self
+    }
+
+    pub fn with_cull_face(mut self, face: Option<Face>) -> Self {
+        self.cull_face = face;
+        self
+    }
+
+    pub fn with_front_face(mut self, front_face: FrontFace) -> Self {
+        self.front_face = front_face;
+        self
+    }
+
+    pub fn with_color_mask(mut self, mask: [bool; 4]) -> Self {
+        self.color_mask = mask;
+        self
+    }
+
+    pub fn with_polygon_offset(mut self, offset: Option<[f32; 2]>) -> Self {
+        self.polygon_offset = offset;
+        self
+    }
+
+    pub fn with_stencil(mut self, stencil: Option<Stencil>) -> Self {
+        self.stencil = stencil;
+        self
+    }
+
+    pub fn with_scissor(mut self, scissor: Option<Viewport>) -> Self {
+        self.scissor = scissor;
+        self
+    }
+
+    /// Same state with every buffer fully writable, clears are affected by masks and scissor.
+    pub(crate) fn for_clear(&self) -> Self {
+        Self {
+            depth_write: true,
+            color_mask: [true; 4],
+            stencil: self.stencil.map(|s| Stencil::separate(
+                s.front.with_masks(s.front.read_mask, !0),
+                s.back.with_masks(s.back.read_mask, !0),
+            )),
+            scissor: None,
+            ..*self
+        }
+    }
+
+    /// Sets this state on the context, only calls for values that differ from the last applied state are issued.
+    pub fn apply(&self, ctx: &Ctx) {
+        let old = ctx.state_cache.borrow().render_state;
+        // parameters of disabled stages are left as they were
+        let mut new = old;
+
+        toggle(ctx, GL::BLEND, self.blend.is_some());
+        if let Some(n) = self.blend {
+            if differs(old.blend, &n, |b| (b.equation_rgb, b.equation_alpha)) {
+                ctx.blend_equation_separate(n.equation_rgb.0, n.equation_alpha.0);
+            }
+            if differs(old.blend, &n, |b| (b.src_rgb, b.dst_rgb, b.src_alpha, b.dst_alpha)) {
+                ctx.blend_func_separate(n.src_rgb.0, n.dst_rgb.0, n.src_alpha.0, n.dst_alpha.0);
+            }
+            if differs(old.blend, &n, |b| b.color) {
+                ctx.blend_color(n.color[0], n.color[1], n.color[2], n.color[3]);
+            }
+            new.blend = Some(n);
+        }
+
+        toggle(ctx, GL::DEPTH_TEST, self.depth_test.is_some());
+        if let Some(func) = self.depth_test.filter(|f| old.depth_test != Some(*f)) {
+            ctx.depth_func(func.0);
+            new.depth_test = Some(func);
+        }
+        if old.depth_write != Some(self.depth_write) {
+            ctx.depth_mask(self.depth_write);
+            new.depth_write = Some(self.depth_write);
+        }
+
+        toggle(ctx, GL::CULL_FACE, self.cull_face.is_some());
+        if let Some(face) = self.cull_face.filter(|f| old.cull_face != Some(*f)) {
+            ctx.cull_face(face.0);
+            new.cull_face = Some(face);
+        }
+        if old.front_face != Some(self.front_face) {
+            ctx.front_face(self.front_face.0);
+            new.front_face = Some(self.front_face);
+        }
+
+        if old.color_mask != Some(self.color_mask) {
+            let [r, g, b, a] = self.color_mask;
+            ctx.color_mask(r, g, b, a);
+            new.color_mask = Some(self.color_mask);
+        }
+
+        toggle(ctx, GL::POLYGON_OFFSET_FILL, self.polygon_offset.is_some());
+        if let Some([factor, units]) = self.polygon_offset.filter(|o| old.polygon_offset != Some(*o)) {
+            ctx.polygon_offset(factor, units);
+            new.polygon_offset = Some([factor, units]);
+        }
+
+        toggle(ctx, GL::STENCIL_TEST, self.stencil.is_some());
+        if let Some(stencil) = self.stencil {
+            apply_stencil(ctx, old.stencil, stencil);
+            new.stencil = Some(stencil);
+        }
+
+        toggle(ctx, GL::SCISSOR_TEST, self.scissor.is_some());
+        if let Some(s) = self.scissor.filter(|s| old.scissor != Some(*s)) {
+            ctx.scissor(s.x, s.y, s.w, s.h);
+            new.scissor = Some(s);
+        }
+
+        ctx.state_cache.borrow_mut().render_state = new;
+    }
+}
+
+/// Capabilities are filtered by the state cache of `Ctx`, so only changes reach GL.
+fn toggle(ctx: &Ctx, cap: u32, enabled: bool) {
+    if enabled {
+        ctx.enable(cap);
+    } else {
+        ctx.disable(cap);
+    }
+}
+
+/// Whether part of the last applied value differs from the new one, unknown values always do.
+fn differs<T, F: PartialEq>(old: Option<T>, new: &T, part: impl Fn(&T) -> F) -> bool {
+    old.is_none_or(|o| part(&o) != part(new))
+}
+
+fn apply_stencil(ctx: &Ctx, old: Option<Stencil>, new: Stencil) {
+    // unknown state is set in full
+    let (old, unknown) = match old {
+        Some(old) => (old, false),
+        None => (new, true),
+    };
+    // both faces are set with a single call when they are the same
+    let faces = if new.front == new.back {
+        vec![(GL::FRONT_AND_BACK, old.front, new.front, unknown || old.front != old.back)]
+    } else {
+        vec![(GL::FRONT, old.front, new.front, unknown), (GL::BACK, old.back, new.back, unknown)]
+    };
+    for (face, o, n, force) in faces {
+        if force || (o.func, o.reference, o.read_mask) != (n.func, n.reference, n.read_mask) {
+            ctx.stencil_func_separate(face, n.func.0, n.reference, n.read_mask);
+        }
+        if force || (o.fail, o.depth_fail, o.pass) != (n.fail, n.depth_fail, n.pass) {
+            ctx.stencil_op_separate(face, n.fail.0, n.depth_fail.0, n.pass.0);
+        }
+        if force || o.write_mask != n.write_mask {
+            ctx.stencil_mask_separate(face, n.write_mask);
+        }
+    }
+}
+
+/// Parameters `RenderState` controls as last set on a context, `None` until first set or after
+/// `Ctx::reset_state_cache`. Whether a stage is enabled is tracked along with other capabilities.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct AppliedState {
+    blend: Option<Blend>,
+    depth_test: Option<CompareFunc>,
+    depth_write: Option<bool>,
+    cull_face: Option<Face>,
+    front_face: Option<FrontFace>,
+    color_mask: Option<[bool; 4]>,
+    polygon_offset: Option<[f32; 2]>,
+    stencil: Option<Stencil>,
+    scissor: Option<Viewport>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Pipeline, Program};
+    use crate::backend::MockBackend;
+    use crate::backend::mock::Call;
+    use crate::texture::EmptyFramebuffer;
+
+    fn setup() -> (MockBackend, Ctx) {
+        let mock = MockBackend::new();
+        let ctx = Ctx::with_backend(mock.clone()).unwrap();
+        (mock, ctx)
+    }
+
+    fn applied(mock: &MockBackend, ctx: &Ctx, state: RenderState) -> Vec<Call> {
+        mock.take_calls();
+        state.apply(ctx);
+        mock.take_calls()
+    }
+
+    fn full() -> RenderState {
+        RenderState::default()
+            .with_blend(Some(Blend::alpha()))
+            .with_stencil(Some(Stencil::new(StencilFace::new(CompareFunc(GL::ALWAYS), 1))))
+            .with_scissor(Some(Viewport::new(4, 4)))
+            .with_polygon_offset(Some([1., 1.]))
+    }
+
+    #[test]
+    fn unchanged_state_issues_no_calls() {
+        let (mock, ctx) = setup();
+        full().apply(&ctx);
+
+        assert_eq!(applied(&mock, &ctx, full()), []);
+    }
+
+    #[test]
+    fn changed_blend_parameter_issues_one_call() {
+        let (mock, ctx) = setup();
+        full().apply(&ctx);
+
+        let tinted = full().with_blend(Some(Blend::alpha().with_color([1.; 4])));
+        assert_eq!(applied(&mock, &ctx, tinted), [Call::BlendColor([1.; 4])]);
+
+        let additive = tinted.with_blend(Some(Blend::additive().with_color([1.; 4])));
+        assert_eq!(applied(&mock, &ctx, additive), [Call::BlendFuncSeparate {
+            src_rgb: GL::ONE, dst_rgb: GL::ONE, src_alpha: GL::ONE, dst_alpha: GL::ONE,
+        }]);
+    }
+
+    #[test]
+    fn stencil_faces_are_set_together_until_they_differ() {
+        let (mock, ctx) = setup();
+        let face = StencilFace::new(CompareFunc(GL::ALWAYS), 1);
+
+        let calls = applied(&mock, &ctx, full());
+        assert!(calls.contains(&Call::StencilFuncSeparate { face: GL::FRONT_AND_BACK, func: GL::ALWAYS, reference: 1, mask: !0 }));
+        assert!(!calls.iter().any(|c| matches!(
+            c,
+            Call::StencilFuncSeparate { face: GL::FRONT, .. } | Call::StencilFuncSeparate { face: GL::BACK, .. }
+        )));
+
+        let separate = full().with_stencil(Some(Stencil::separate(face, StencilFace::new(CompareFunc(GL::EQUAL), 1))));
+        assert_eq!(applied(&mock, &ctx, separate), [
+            Call::StencilFuncSeparate { face: GL::BACK, func: GL::EQUAL, reference: 1, mask: !0 },
+        ]);
+    }
+
+    #[test]
+    fn changed_scissor_and_polygon_offset_issue_one_call_each() {
+        let (mock, ctx) = setup();
+        full().apply(&ctx);
+
+        let moved = full().with_scissor(Some(Viewport::new(2, 2)));
+        assert_eq!(applied(&mock, &ctx, moved), [Call::Scissor(0, 0, 2, 2)]);
+
+        let offset = moved.with_polygon_offset(Some([2., 1.]));
+        assert_eq!(applied(&mock, &ctx, offset), [Call::PolygonOffset { factor: 2., units: 1. }]);
+    }
+
+    #[test]
+    fn clear_is_not_limited_by_scissor() {
+        let (mock, ctx) = setup();
+        let program = Program::new(&ctx, "void main() {}", "void main() {}").unwrap();
+        let mut output = EmptyFramebuffer::new(&ctx, Viewport::new(4, 4));
+        let scissored = RenderState::default().with_scissor(Some(Viewport::new(2, 2)));
+        let mut pipeline = Pipeline::new(&ctx).with_clear_color(Some([0., 0., 0., 1.])).with_render_state(scissored);
+        // left enabled by the previous pass
+        scissored.apply(&ctx);
+        mock.take_calls();
+
+        pipeline.shade(&program, vec![], vec![], &mut output).unwrap();
+
+        let calls = mock.take_calls();
+        let position = |call: &Call| calls.iter().position(|c| c == call).unwrap();
+        let clear = calls.iter().position(|c| matches!(c, Call::Clear(_))).unwrap();
+        assert!(position(&Call::Disable(GL::SCISSOR_TEST)) < clear);
+        assert!(clear < position(&Call::Enable(GL::SCISSOR_TEST)));
+    }
+}
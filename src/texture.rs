@@ -1,8 +1,8 @@
-use web_sys::{WebGlFramebuffer, WebGlTexture};
+use crate::{GL, Ctx, Error};
+use crate::backend::{FramebufferId, TextureId};
+use crate::error::ResourceKind;
 
-use crate::{GL, Ctx};
-
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Viewport {
     pub x: i32,
     pub y: i32,
@@ -32,7 +32,7 @@ impl Viewport {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct ColorFormat(pub u32);
 
 impl From<ColorFormat> for i32 {
@@ -47,7 +47,7 @@ impl From<ColorFormat> for u32 {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct InterpolationMin(pub u32);
 
 impl From<InterpolationMin> for i32 {
@@ -56,7 +56,7 @@ impl From<InterpolationMin> for i32 {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct InterpolationMag(pub u32);
 
 impl From<InterpolationMag> for i32 {
@@ -65,7 +65,7 @@ impl From<InterpolationMag> for i32 {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct WrapT(pub u32);
 
 impl From<WrapT> for i32 {
@@ -74,7 +74,7 @@ impl From<WrapT> for i32 {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct WrapS(pub u32);
 
 
@@ -84,7 +84,7 @@ impl From<WrapS> for i32 {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct InternalFormat(pub u32);
 
 impl From<InternalFormat> for u32 {
@@ -93,6 +93,7 @@ impl From<InternalFormat> for u32 {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct TextureSpec {
     pub color_format: ColorFormat,
     pub dimensions: [u32; 2],
@@ -146,39 +147,33 @@ impl TextureSpec {
         self
     }
 
-    pub fn upload_u8(&self, ctx: &Ctx, data: &[u8]) -> Result<UploadedTexture, String> {
-        let arr = js_sys::Uint8Array::new_with_length(data.len() as u32);
-        arr.copy_from(data);
-        self.upload(ctx, InternalFormat(GL::UNSIGNED_BYTE), Some(&arr))
+    pub fn upload_u8(&self, ctx: &Ctx, data: &[u8]) -> Result<UploadedTexture, Error> {
+        self.upload(ctx, InternalFormat(GL::UNSIGNED_BYTE), Some(data))
     }
 
-    pub fn upload_rgba(&self, ctx: &Ctx, data: &[[f32; 4]]) -> Result<UploadedTexture, String> {
+    pub fn upload_rgba(&self, ctx: &Ctx, data: &[[f32; 4]]) -> Result<UploadedTexture, Error> {
         self.upload_f32(ctx, &data.iter().flat_map(|v| v.to_vec()).collect::<Vec<f32>>())
     }
 
-    pub fn upload_f32(&self, ctx: &Ctx, data: &[f32]) -> Result<UploadedTexture, String> {
-        let arr = js_sys::Float32Array::new_with_length(data.len() as u32);
-        arr.copy_from(data);
-        self.upload(ctx, InternalFormat(GL::FLOAT), Some(&arr))
+    pub fn upload_f32(&self, ctx: &Ctx, data: &[f32]) -> Result<UploadedTexture, Error> {
+        let bytes = data.iter().flat_map(|e| e.to_ne_bytes()).collect::<Vec<u8>>();
+        self.upload(ctx, InternalFormat(GL::FLOAT), Some(&bytes))
     }
 
-    pub fn upload(&self, ctx: &Ctx, internal_format: InternalFormat, data: Option<&js_sys::Object>) -> Result<UploadedTexture, String> {
-        let handle = ctx
-            .create_texture()
-            .ok_or("Failed to create texture")?;
-        ctx.bind_texture(GL::TEXTURE_2D, Some(&handle));
-        ctx.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_array_buffer_view(
+    pub fn upload(&self, ctx: &Ctx, internal_format: InternalFormat, data: Option<&[u8]>) -> Result<UploadedTexture, Error> {
+        let handle = ctx.resource(ctx.create_texture(), ResourceKind::Texture)?;
+        ctx.bind_texture(GL::TEXTURE_2D, Some(handle));
+        ctx.tex_image_2d(
             GL::TEXTURE_2D,
             0,
             self.color_format.into(),
             self.dimensions[0] as i32,
             self.dimensions[1] as i32,
-            0,
             self.color_format.into(),
             internal_format.into(),
             data,
         )
-        .map_err(|e| format!("Failed to send image data {:?}", e))?;
+        .map_err(Error::TextureUpload)?;
 
         ctx.tex_parameteri(
             GL::TEXTURE_2D,
@@ -204,13 +199,17 @@ impl TextureSpec {
 
 pub struct UploadedTexture {
     ctx: Ctx,
-    handle: WebGlTexture,
+    handle: TextureId,
     size: [u32; 2],
 }
 
 impl UploadedTexture {
-    pub fn bind(&mut self) {
-        self.ctx.bind_texture(GL::TEXTURE_2D, Some(&self.handle));
+    pub fn bind(&self) {
+        self.ctx.bind_texture(GL::TEXTURE_2D, Some(self.handle));
+    }
+
+    pub fn id(&self) -> TextureId {
+        self.handle
     }
 
     pub fn sizef32(&self) -> [f32; 2] {
@@ -220,7 +219,7 @@ impl UploadedTexture {
 
 impl Drop for UploadedTexture {
     fn drop(&mut self) {
-        self.ctx.delete_texture(Some(&self.handle));
+        self.ctx.delete_texture(self.handle);
     }
 }
 
@@ -266,11 +265,11 @@ impl EmptyFramebuffer {
         self.viewport.set(&self.ctx);
     }
 
-    pub fn with_color_slot(self, handle: UploadedTexture) -> Result<ColorFramebuffer, String> {
+    pub fn with_color_slot(self, handle: UploadedTexture) -> Result<ColorFramebuffer, Error> {
         Ok(ColorFramebuffer { fb: FramebufferWithSlot::from_fb(self, FramebufferSlot::Color, handle)? })
     }
 
-    pub fn with_depth_slot(self, handle: UploadedTexture) -> Result<DepthFrameBuffer, String> {
+    pub fn with_depth_slot(self, handle: UploadedTexture) -> Result<DepthFrameBuffer, Error> {
         Ok(DepthFrameBuffer { fb: FramebufferWithSlot::from_fb(self, FramebufferSlot::Depth, handle)? })
     }
 }
@@ -278,15 +277,13 @@ impl EmptyFramebuffer {
 struct FramebufferWithSlot {
     ctx: Ctx,
     viewport: Viewport,
-    handle: WebGlFramebuffer,
+    handle: FramebufferId,
     slot: UploadedTexture,
 }
 
 impl FramebufferWithSlot {
-    fn from_fb(fb: EmptyFramebuffer, attachment: FramebufferSlot, handle: UploadedTexture) -> Result<FramebufferWithSlot, String> {
-         let fb_handle = fb.ctx
-             .create_framebuffer()
-             .ok_or("Failed to create frame buffer")?;
+    fn from_fb(fb: EmptyFramebuffer, attachment: FramebufferSlot, handle: UploadedTexture) -> Result<FramebufferWithSlot, Error> {
+        let fb_handle = fb.ctx.resource(fb.ctx.create_framebuffer(), ResourceKind::Framebuffer)?;
 
         let mut result = Self {
             ctx: fb.ctx,
@@ -301,16 +298,19 @@ impl FramebufferWithSlot {
             GL::FRAMEBUFFER,
             attachment.into(),
             GL::TEXTURE_2D,
-            Some(&result.slot.handle),
+            Some(result.slot.handle),
             0,
         );
 
-        Ok(result)
+        match result.ctx.check_framebuffer_status(GL::FRAMEBUFFER) {
+            GL::FRAMEBUFFER_COMPLETE => Ok(result),
+            status => Err(Error::IncompleteFramebuffer(status)),
+        }
     }
 
     fn bind(&mut self) {
         self.ctx.bind_texture(GL::TEXTURE_2D, None);
-        self.ctx.bind_framebuffer(GL::FRAMEBUFFER, Some(&self.handle));
+        self.ctx.bind_framebuffer(GL::FRAMEBUFFER, Some(self.handle));
         self.viewport.set(&self.ctx);
     }
 }
@@ -367,6 +367,6 @@ impl Framebuffer for DepthFrameBuffer {
 
 impl Drop for FramebufferWithSlot {
     fn drop(&mut self) {
-        self.ctx.delete_framebuffer(Some(&self.handle));
+        self.ctx.delete_framebuffer(self.handle);
     }
 }
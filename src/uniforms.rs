@@ -0,0 +1,92 @@
+use crate::UniformData;
+use crate::texture::UploadedTexture;
+
+/// Set of uniform values, usually implemented with `#[derive(Uniforms)]` (`derive` feature).
+///
+/// ```ignore
+/// #[derive(Uniforms)]
+/// struct Material {
+///     #[uniform(rename = "u_color")]
+///     color: [f32; 4],
+///     roughness: f32,
+///     albedo: UploadedTexture,
+/// }
+///
+/// pipeline.shade(&program, material.uniforms(), vec![&mut mesh], &mut output)?;
+/// ```
+pub trait Uniforms {
+    fn uniforms(&mut self) -> Vec<(&'static str, UniformData<'_>)>;
+}
+
+/// Value that can be uploaded as a uniform, `[f32; 4]` maps to `vec4`, use `UniformData::Matrix2` for `mat2`.
+pub trait AsUniformData {
+    fn as_uniform_data(&mut self) -> UniformData<'_>;
+}
+
+macro_rules! as_uniform_data {
+    ($ty:ty, $variant:ident) => {
+        impl AsUniformData for $ty {
+            fn as_uniform_data(&mut self) -> UniformData<'_> {
+                UniformData::$variant(*self)
+            }
+        }
+    };
+    ($ty:ty, $variant:ident, slice) => {
+        impl AsUniformData for Vec<$ty> {
+            fn as_uniform_data(&mut self) -> UniformData<'_> {
+                UniformData::$variant(self)
+            }
+        }
+
+        impl AsUniformData for &[$ty] {
+            fn as_uniform_data(&mut self) -> UniformData<'_> {
+                UniformData::$variant(self)
+            }
+        }
+    };
+}
+
+as_uniform_data!(f32, Scalar);
+as_uniform_data!([f32; 2], Vector2);
+as_uniform_data!([f32; 3], Vector3);
+as_uniform_data!([f32; 4], Vector4);
+as_uniform_data!(i32, Int);
+as_uniform_data!([i32; 2], IntVector2);
+as_uniform_data!([i32; 3], IntVector3);
+as_uniform_data!([i32; 4], IntVector4);
+as_uniform_data!(bool, Bool);
+as_uniform_data!([bool; 2], BoolVector2);
+as_uniform_data!([bool; 3], BoolVector3);
+as_uniform_data!([bool; 4], BoolVector4);
+as_uniform_data!([f32; 9], Matrix3);
+as_uniform_data!([f32; 16], Matrix4);
+
+as_uniform_data!(f32, ScalarArray, slice);
+as_uniform_data!([f32; 2], Vector2Array, slice);
+as_uniform_data!([f32; 3], Vector3Array, slice);
+as_uniform_data!([f32; 4], Vector4Array, slice);
+as_uniform_data!(i32, IntArray, slice);
+as_uniform_data!([i32; 2], IntVector2Array, slice);
+as_uniform_data!([i32; 3], IntVector3Array, slice);
+as_uniform_data!([i32; 4], IntVector4Array, slice);
+as_uniform_data!(bool, BoolArray, slice);
+as_uniform_data!([f32; 9], Matrix3Array, slice);
+as_uniform_data!([f32; 16], Matrix4Array, slice);
+
+impl AsUniformData for UploadedTexture {
+    fn as_uniform_data(&mut self) -> UniformData<'_> {
+        UniformData::Texture(self)
+    }
+}
+
+impl AsUniformData for &mut UploadedTexture {
+    fn as_uniform_data(&mut self) -> UniformData<'_> {
+        UniformData::Texture(self)
+    }
+}
+
+impl AsUniformData for Vec<&UploadedTexture> {
+    fn as_uniform_data(&mut self) -> UniformData<'_> {
+        UniformData::TextureArray(self)
+    }
+}
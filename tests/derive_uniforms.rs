@@ -0,0 +1,44 @@
+//! Output of `#[derive(Uniforms)]` checked against a program reflected by `MockBackend`.
+#![cfg(all(feature = "derive", feature = "testing"))]
+
+use glsmrs::{Ctx, Program, UniformData, Uniforms, GL};
+use glsmrs::backend::MockBackend;
+use glsmrs::texture::{ColorFormat, InternalFormat, TextureSpec, UploadedTexture};
+
+const VERTEX: &str = "attribute vec2 position; void main() {}";
+const FRAGMENT: &str = "
+struct Light { vec4 tint; mat2 rotation; sampler2D shadow; };
+uniform Light u_light;
+void main() {}
+";
+
+#[derive(Uniforms)]
+#[uniform(prefix = "u_light.")]
+struct Light<'a> {
+    #[uniform(rename = "tint")]
+    color: [f32; 4],
+    #[uniform(mat2)]
+    rotation: [f32; 4],
+    #[uniform(skip)]
+    #[allow(dead_code)]
+    cached: u32,
+    shadow: &'a mut UploadedTexture,
+}
+
+#[test]
+fn derived_uniforms_are_named_and_typed_from_attributes() {
+    let ctx = Ctx::with_backend(MockBackend::new()).unwrap();
+    let mut shadow = TextureSpec::new(ColorFormat(GL::RGBA), [1, 1]).upload(&ctx, InternalFormat(GL::RGBA), None).unwrap();
+    let mut light = Light { color: [1.; 4], rotation: [0., 1., -1., 0.], cached: 0, shadow: &mut shadow };
+
+    let uniforms = light.uniforms();
+    let names = uniforms.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+    assert_eq!(names, ["u_light.tint", "u_light.rotation", "u_light.shadow"]);
+    assert!(matches!(uniforms[0].1, UniformData::Vector4([1., 1., 1., 1.])));
+    assert!(matches!(uniforms[1].1, UniformData::Matrix2([0., 1., -1., 0.])));
+    assert!(matches!(uniforms[2].1, UniformData::Texture(_)));
+    drop(uniforms);
+
+    let program = Program::new(&ctx, VERTEX, FRAGMENT).unwrap();
+    assert_eq!(program.validate(&mut light), Ok(()));
+}
@@ -0,0 +1,32 @@
+//! Layout produced by `#[derive(Clone, Copy, Vertex)]`, compile failures live in `tests/ui`.
+#![cfg(feature = "derive")]
+
+use glsmrs::GL;
+use glsmrs::attributes::{Vertex, VertexAttribute};
+
+#[repr(C)]
+#[derive(Clone, Copy, Vertex)]
+#[allow(dead_code)]
+struct Particle {
+    #[vertex(rename = "a_position")]
+    position: [f32; 2],
+    #[vertex(normalized)]
+    color: [u8; 4],
+    size: f32,
+    #[vertex(skip)]
+    age: f32,
+}
+
+#[test]
+fn attributes_follow_field_layout() {
+    assert_eq!(Particle::attributes(), vec![
+        VertexAttribute { name: "a_position", size: 2, data_type: GL::FLOAT, normalized: false, offset: 0, columns: 1 },
+        VertexAttribute { name: "color", size: 4, data_type: GL::UNSIGNED_BYTE, normalized: true, offset: 8, columns: 1 },
+        VertexAttribute { name: "size", size: 1, data_type: GL::FLOAT, normalized: false, offset: 12, columns: 1 },
+    ]);
+}
+
+#[test]
+fn invalid_vertex_structs_do_not_compile() {
+    trybuild::TestCases::new().compile_fail("tests/ui/vertex_*.rs");
+}
@@ -0,0 +1,107 @@
+//! `NativeBackend` against a headless surfaceless EGL context (Mesa llvmpipe works fine).
+//! The test is skipped when libEGL or the surfaceless platform is not available.
+#![cfg(feature = "glow")]
+
+use glow::HasContext;
+use khronos_egl as egl;
+
+use glsmrs::{Ctx, Pipeline, Program, UniformData, GL};
+use glsmrs::attributes::AttributeVector2;
+use glsmrs::backend::NativeBackend;
+use glsmrs::mesh::Mesh;
+use glsmrs::texture::{ColorFormat, EmptyFramebuffer, Framebuffer, TextureSpec, Viewport};
+
+const PLATFORM_SURFACELESS_MESA: egl::Enum = 0x31DD;
+
+const VERTEX: &str = "
+attribute vec2 position;
+void main() {
+    gl_Position = vec4(position, 0.0, 1.0);
+}
+";
+
+const FRAGMENT: &str = "
+precision mediump float;
+uniform vec4 u_color;
+void main() {
+    gl_FragColor = u_color;
+}
+";
+
+struct Headless {
+    egl: egl::DynamicInstance<egl::EGL1_5>,
+    display: egl::Display,
+    context: egl::Context,
+}
+
+impl Headless {
+    fn new() -> Result<Self, String> {
+        let egl = unsafe { egl::DynamicInstance::<egl::EGL1_5>::load_required() }.map_err(|e| e.to_string())?;
+        let display = unsafe { egl.get_platform_display(PLATFORM_SURFACELESS_MESA, egl::DEFAULT_DISPLAY, &[egl::ATTRIB_NONE]) }
+            .map_err(|e| e.to_string())?;
+        egl.initialize(display).map_err(|e| e.to_string())?;
+        egl.bind_api(egl::OPENGL_ES_API).map_err(|e| e.to_string())?;
+        let config = egl
+            .choose_first_config(display, &[egl::SURFACE_TYPE, 0, egl::RENDERABLE_TYPE, egl::OPENGL_ES2_BIT, egl::NONE])
+            .map_err(|e| e.to_string())?
+            .ok_or("no GLES2 config")?;
+        let context = egl
+            .create_context(display, config, None, &[egl::CONTEXT_CLIENT_VERSION, 2, egl::NONE])
+            .map_err(|e| e.to_string())?;
+        egl.make_current(display, None, None, Some(context)).map_err(|e| e.to_string())?;
+
+        Ok(Self { egl, display, context })
+    }
+
+    fn gl(&self) -> glow::Context {
+        unsafe {
+            glow::Context::from_loader_function(|name| {
+                self.egl.get_proc_address(name).map_or(std::ptr::null(), |p| p as *const _)
+            })
+        }
+    }
+}
+
+impl Drop for Headless {
+    fn drop(&mut self) {
+        let _ = self.egl.make_current(self.display, None, None, None);
+        let _ = self.egl.destroy_context(self.display, self.context);
+        let _ = self.egl.terminate(self.display);
+    }
+}
+
+#[test]
+fn renders_triangle_into_texture() {
+    let headless = match Headless::new() {
+        Ok(h) => h,
+        Err(e) => {
+            eprintln!("skipping, headless EGL context is not available: {}", e);
+            return;
+        }
+    };
+    // second set of function pointers for reading back, the backend owns the first one
+    let reader = headless.gl();
+    let ctx = Ctx::with_backend(unsafe { NativeBackend::new(headless.gl()) }).unwrap();
+
+    let program = Program::new(&ctx, VERTEX, FRAGMENT).unwrap();
+    let mut mesh = Mesh::new(&ctx, &[0, 1, 2]).unwrap()
+        .with_attribute::<AttributeVector2>("position", &[[-1., -1.], [1., -1.], [-1., 1.]]).unwrap();
+    let color = TextureSpec::new(ColorFormat(GL::RGBA), [16, 16]).upload_u8(&ctx, &[0; 16 * 16 * 4]).unwrap();
+    let mut output = EmptyFramebuffer::new(&ctx, Viewport::new(16, 16)).with_color_slot(color).unwrap();
+
+    Pipeline::new(&ctx)
+        .with_clear_color(Some([0., 0., 1., 1.]))
+        .shade(&program, vec![("u_color", UniformData::Vector4([1., 0., 0., 1.]))], vec![&mut mesh], &mut output)
+        .unwrap();
+
+    output.bind();
+    let mut pixels = vec![0u8; 16 * 16 * 4];
+    unsafe {
+        reader.read_pixels(0, 0, 16, 16, GL::RGBA, GL::UNSIGNED_BYTE, glow::PixelPackData::Slice(Some(&mut pixels)));
+    }
+    let pixel = |x: usize, y: usize| &pixels[(y * 16 + x) * 4..(y * 16 + x) * 4 + 4];
+
+    // triangle covers lower left half, rows are read bottom to top
+    assert_eq!(pixel(2, 2), [255, 0, 0, 255]);
+    assert_eq!(pixel(13, 13), [0, 0, 255, 255]);
+}
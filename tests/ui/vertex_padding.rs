@@ -0,0 +1,10 @@
+use glsmrs::attributes::Vertex;
+
+#[repr(C)]
+#[derive(Clone, Copy, Vertex)]
+struct Padded {
+    flag: u8,
+    position: [f32; 2],
+}
+
+fn main() {}
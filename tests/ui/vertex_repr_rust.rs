@@ -0,0 +1,9 @@
+use glsmrs::attributes::Vertex;
+
+#[derive(Clone, Copy, Vertex)]
+struct Unordered {
+    position: [f32; 2],
+    size: f32,
+}
+
+fn main() {}